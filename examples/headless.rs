@@ -0,0 +1,86 @@
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::math::DVec3;
+use bevy::prelude::*;
+use bevy_terrain::prelude::*;
+use bevy_terrain::terrain_data::sample_height;
+use std::time::Duration;
+
+const PATH: &str = "terrains/planar";
+const TERRAIN_SIZE: f64 = 2000.0;
+const HEIGHT: f32 = 500.0;
+const TEXTURE_SIZE: u32 = 512;
+const LOD_COUNT: u32 = 4;
+
+#[derive(Resource)]
+struct Viewer(Entity, Entity);
+
+fn main() {
+    App::new()
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(100))),
+            LogPlugin::default(),
+            TransformPlugin,
+            TerrainDataPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .add_systems(Update, move_viewer)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    let config = TerrainConfig {
+        lod_count: LOD_COUNT,
+        model: TerrainModel::planar(DVec3::new(0.0, -100.0, 0.0), TERRAIN_SIZE, 0.0, HEIGHT),
+        path: PATH.to_string(),
+        ..default()
+    }
+    .add_attachment(AttachmentConfig {
+        name: "height".to_string(),
+        texture_size: TEXTURE_SIZE,
+        border_size: 2,
+        mip_level_count: 4,
+        format: AttachmentFormat::R16,
+        ..default()
+    });
+
+    let terrain = commands
+        .spawn(TerrainBundle::new(TileAtlas::new(&config)))
+        .id();
+
+    // Any entity with a transform can request the terrain around it,
+    // e.g. a player on a dedicated server.
+    let view = commands
+        .spawn((TransformBundle::default(), TerrainProbe::default()))
+        .id();
+
+    commands.insert_resource(Viewer(terrain, view));
+}
+
+fn move_viewer(
+    time: Res<Time>,
+    viewer: Res<Viewer>,
+    tile_trees: Res<TerrainViewComponents<TileTree>>,
+    tile_atlases: Query<&TileAtlas>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let Viewer(terrain, view) = *viewer;
+
+    let mut transform = transforms.get_mut(view).unwrap();
+    let t = time.elapsed_seconds() * 0.1;
+    transform.translation = Vec3::new(
+        (0.5 + 0.4 * t.cos()) * TERRAIN_SIZE as f32,
+        HEIGHT,
+        (0.5 + 0.4 * t.sin()) * TERRAIN_SIZE as f32,
+    );
+
+    let Some(tile_tree) = tile_trees.get(&(terrain, view)) else {
+        return;
+    };
+    let tile_atlas = tile_atlases.get(terrain).unwrap();
+
+    let position = transform.translation.as_dvec3();
+    let height = sample_height(tile_tree, tile_atlas, position);
+
+    info!("Height at {position:.1}: {height:.2}");
+}
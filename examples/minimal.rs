@@ -23,7 +23,6 @@ fn main() {
 fn setup(
     mut commands: Commands,
     mut materials: ResMut<Assets<DebugTerrainMaterial>>,
-    mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
     mut meshes: ResMut<Assets<Mesh>>,
 ) {
     // Configure all the important properties of the terrain, as well as its attachments.
@@ -39,24 +38,18 @@ fn setup(
         border_size: 2,
         mip_level_count: 4,
         format: AttachmentFormat::R16,
+        ..default()
     });
 
     // Configure the quality settings of the terrain view. Adapt the settings to your liking.
     let view_config = TerrainViewConfig::default();
 
-    let tile_atlas = TileAtlas::new(&config);
-    let tile_tree = TileTree::new(&tile_atlas, &view_config);
-
-    let terrain = commands
-        .spawn((
-            TerrainBundle::new(tile_atlas),
-            materials.add(DebugTerrainMaterial::default()),
-        ))
-        .id();
-
-    let view = commands.spawn(DebugCameraBundle::default()).id();
+    commands.spawn((
+        TerrainBundle::new(TileAtlas::new(&config)),
+        materials.add(DebugTerrainMaterial::default()),
+    ));
 
-    tile_trees.insert((terrain, view), tile_tree);
+    commands.spawn((DebugCameraBundle::default(), TerrainView::new(view_config)));
 
     commands.spawn(PbrBundle {
         mesh: meshes.add(Cuboid::from_length(10.0)),
@@ -37,7 +37,6 @@ fn setup(
     mut commands: Commands,
     mut images: ResMut<LoadingImages>,
     mut materials: ResMut<Assets<TerrainMaterial>>,
-    mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
     asset_server: Res<AssetServer>,
 ) {
     let gradient = asset_server.load("textures/gradient2.png");
@@ -60,6 +59,7 @@ fn setup(
         border_size: 2,
         mip_level_count: 4,
         format: AttachmentFormat::R16,
+        ..default()
     })
     .add_attachment(AttachmentConfig {
         name: "albedo".to_string(),
@@ -67,26 +67,22 @@ fn setup(
         border_size: 2,
         mip_level_count: 4,
         format: AttachmentFormat::Rgba8,
+        ..default()
     });
 
     // Configure the quality settings of the terrain view. Adapt the settings to your liking.
     let view_config = TerrainViewConfig::default();
 
     let tile_atlas = TileAtlas::new(&config);
-    let tile_tree = TileTree::new(&tile_atlas, &view_config);
 
     commands.spawn_big_space(ReferenceFrame::default(), |root| {
         let frame = root.frame().clone();
 
-        let terrain = root
-            .spawn_spatial((
-                TerrainBundle::new(tile_atlas, &frame),
-                materials.add(TerrainMaterial { gradient }),
-            ))
-            .id();
-
-        let view = root.spawn_spatial(DebugCameraBundle::default()).id();
+        root.spawn_spatial((
+            TerrainBundle::new(tile_atlas, &frame),
+            materials.add(TerrainMaterial { gradient }),
+        ));
 
-        tile_trees.insert((terrain, view), tile_tree);
+        root.spawn_spatial((DebugCameraBundle::default(), TerrainView::new(view_config)));
     });
 }
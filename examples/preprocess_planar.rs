@@ -1,7 +1,9 @@
-use bevy::prelude::*;
+use bevy::{math::DVec3, prelude::*};
 use bevy_terrain::prelude::*;
 
 const PATH: &str = "terrains/planar";
+const TERRAIN_SIZE: f64 = 2000.0;
+const HEIGHT: f32 = 500.0;
 const TEXTURE_SIZE: u32 = 512;
 const LOD_COUNT: u32 = 4;
 
@@ -15,6 +17,7 @@ fn main() {
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let config = TerrainConfig {
         lod_count: LOD_COUNT,
+        model: TerrainModel::planar(DVec3::new(0.0, -100.0, 0.0), TERRAIN_SIZE, 0.0, HEIGHT),
         path: PATH.to_string(),
         ..default()
     }
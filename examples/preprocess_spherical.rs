@@ -1,7 +1,11 @@
-use bevy::prelude::*;
+use bevy::{math::DVec3, prelude::*};
 use bevy_terrain::prelude::*;
 
 const PATH: &str = "terrains/spherical";
+const MAJOR_AXES: f64 = 6378137.0;
+const MINOR_AXES: f64 = 6356752.314245;
+const MIN_HEIGHT: f32 = -12000.0;
+const MAX_HEIGHT: f32 = 9000.0;
 const TEXTURE_SIZE: u32 = 512;
 const LOD_COUNT: u32 = 5;
 
@@ -19,6 +23,7 @@ fn main() {
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     let config = TerrainConfig {
         lod_count: LOD_COUNT,
+        model: TerrainModel::ellipsoid(DVec3::ZERO, MAJOR_AXES, MINOR_AXES, MIN_HEIGHT, MAX_HEIGHT),
         path: PATH.to_string(),
         atlas_size: 2048,
         ..default()
@@ -27,6 +32,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         name: "height".to_string(),
         texture_size: TEXTURE_SIZE,
         border_size: 2,
+        mip_level_count: 4,
         format: AttachmentFormat::R16,
         ..default()
     });
@@ -3,11 +3,6 @@ use bevy_terrain::prelude::*;
 
 const PATH: &str = "terrains/spherical";
 const RADIUS: f64 = 6371000.0;
-const MAJOR_AXES: f64 = 6378137.0;
-const MINOR_AXES: f64 = 6356752.314245;
-const MIN_HEIGHT: f32 = -12000.0;
-const MAX_HEIGHT: f32 = 9000.0;
-const TEXTURE_SIZE: u32 = 512;
 const LOD_COUNT: u32 = 16;
 
 #[derive(Asset, AsBindGroup, TypePath, Clone)]
@@ -41,7 +36,6 @@ fn setup(
     mut images: ResMut<LoadingImages>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<TerrainMaterial>>,
-    mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
     asset_server: Res<AssetServer>,
 ) {
     let gradient = asset_server.load("textures/gradient.png");
@@ -51,56 +45,32 @@ fn setup(
         TextureFormat::Rgba8UnormSrgb,
     );
 
-    // Configure all the important properties of the terrain, as well as its attachments.
+    // Load the model and the attachments of the terrain from the manifest written by the preprocessor.
     let config = TerrainConfig {
         lod_count: LOD_COUNT,
-        model: TerrainModel::ellipsoid(DVec3::ZERO, MAJOR_AXES, MINOR_AXES, MIN_HEIGHT, MAX_HEIGHT),
-        // model: TerrainModel::ellipsoid(
-        //     DVec3::ZERO,
-        //     6378137.0,
-        //     6378137.0 * 0.5,
-        //     MIN_HEIGHT,
-        //     MAX_HEIGHT,
-        // ),
-        // model: TerrainModel::sphere(DVec3::ZERO, RADIUS),
-        path: PATH.to_string(),
-        ..default()
-    }
-    .add_attachment(AttachmentConfig {
-        name: "height".to_string(),
-        texture_size: TEXTURE_SIZE,
-        border_size: 2,
-        mip_level_count: 4,
-        format: AttachmentFormat::R16,
-    });
+        ..TerrainConfig::load_from_manifest(PATH)
+            .expect("Run the preprocess_spherical example first.")
+    };
 
     // Configure the quality settings of the terrain view. Adapt the settings to your liking.
     let view_config = TerrainViewConfig::default();
 
     let tile_atlas = TileAtlas::new(&config);
-    let tile_tree = TileTree::new(&tile_atlas, &view_config);
 
     commands.spawn_big_space(ReferenceFrame::default(), |root| {
         let frame = root.frame().clone();
 
-        let terrain = root
-            .spawn_spatial((
-                TerrainBundle::new(tile_atlas, &frame),
-                materials.add(TerrainMaterial {
-                    gradient: gradient.clone(),
-                }),
-            ))
-            .id();
-
-        let view = root
-            .spawn_spatial(DebugCameraBundle::new(
-                -DVec3::X * RADIUS * 3.0,
-                RADIUS,
-                &frame,
-            ))
-            .id();
+        root.spawn_spatial((
+            TerrainBundle::new(tile_atlas, &frame),
+            materials.add(TerrainMaterial {
+                gradient: gradient.clone(),
+            }),
+        ));
 
-        tile_trees.insert((terrain, view), tile_tree);
+        root.spawn_spatial((
+            DebugCameraBundle::new(-DVec3::X * RADIUS * 3.0, RADIUS, &frame),
+            TerrainView::new(view_config),
+        ));
 
         let sun_position = DVec3::new(-1.0, 1.0, -1.0) * RADIUS * 10.0;
         let (sun_cell, sun_translation) = frame.translation_to_grid(sun_position);
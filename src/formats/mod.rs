@@ -1,25 +1,261 @@
 pub mod tiff;
 
-use crate::math::TileCoordinate;
-use anyhow::Result;
+use crate::{
+    math::{TerrainKind, TerrainModel, TileCoordinate},
+    terrain::TerrainConfig,
+    terrain_data::{tile_source::TileSource, AttachmentConfig, AttachmentData, AttachmentStorage},
+};
+use anyhow::{bail, Result};
+use bevy::{math::DVec3, tasks::block_on};
 use bincode::{config, Decode, Encode};
-use std::{fs, path::Path};
+use std::{fmt::Debug, fs, iter, path::Path};
 
+/// The version of the [`TerrainManifest`] format.
+/// This has to be incremented, whenever the layout of the manifest or the stored tiles changes.
+pub const MANIFEST_VERSION: u32 = 4;
+
+/// The encodable shape of a [`TerrainModel`].
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub enum ModelShape {
+    Planar {
+        position: [f64; 3],
+        side_length: f64,
+    },
+    Spherical {
+        position: [f64; 3],
+        radius: f64,
+    },
+    Ellipsoidal {
+        position: [f64; 3],
+        major_axis: f64,
+        minor_axis: f64,
+    },
+}
+
+impl ModelShape {
+    fn is_spherical(&self) -> bool {
+        !matches!(self, ModelShape::Planar { .. })
+    }
+}
+
+/// The self-describing manifest of a preprocessed terrain.
+///
+/// It is written alongside the tiles and records everything required to interpret them.
 #[derive(Encode, Decode, Debug)]
-pub struct TC {
+pub struct TerrainManifest {
+    /// The count of level of detail layers that were preprocessed.
+    pub lod_count: u32,
+    pub shape: ModelShape,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// The attachments of the terrain.
+    pub attachments: Vec<AttachmentConfig>,
+    /// The [`TileCoordinate`]s of all the tiles of the terrain.
     pub tiles: Vec<TileCoordinate>,
 }
 
-impl TC {
+impl TerrainManifest {
+    pub fn new(
+        lod_count: u32,
+        model: &TerrainModel,
+        attachments: Vec<AttachmentConfig>,
+        tiles: Vec<TileCoordinate>,
+    ) -> Self {
+        let position = model.translation.to_array();
+
+        let shape = match model.kind {
+            TerrainKind::PLANAR { side_length } => ModelShape::Planar {
+                position,
+                side_length,
+            },
+            TerrainKind::SPHERICAL { radius } => ModelShape::Spherical { position, radius },
+            TerrainKind::ELLIPSOIDAL {
+                major_axis,
+                minor_axis,
+                ..
+            } => ModelShape::Ellipsoidal {
+                position,
+                major_axis,
+                minor_axis,
+            },
+        };
+
+        Self {
+            lod_count,
+            shape,
+            min_height: model.min_height,
+            max_height: model.max_height,
+            attachments,
+            tiles,
+        }
+    }
+
+    /// The path of the manifest inside the terrain folder.
+    pub fn path(terrain_path: &str) -> String {
+        format!("assets/{terrain_path}/manifest.tm")
+    }
+
+    /// Reconstructs the [`TerrainModel`] the terrain was preprocessed with.
+    pub fn model(&self) -> TerrainModel {
+        match self.shape {
+            ModelShape::Planar {
+                position,
+                side_length,
+            } => TerrainModel::planar(
+                DVec3::from_array(position),
+                side_length,
+                self.min_height,
+                self.max_height,
+            ),
+            ModelShape::Spherical { position, radius } => TerrainModel::sphere(
+                DVec3::from_array(position),
+                radius,
+                self.min_height,
+                self.max_height,
+            ),
+            ModelShape::Ellipsoidal {
+                position,
+                major_axis,
+                minor_axis,
+            } => TerrainModel::ellipsoid(
+                DVec3::from_array(position),
+                major_axis,
+                minor_axis,
+                self.min_height,
+                self.max_height,
+            ),
+        }
+    }
+
+    /// Checks whether the stored tiles can be interpreted with the terrain config.
+    ///
+    /// The placement, the size and the height range of the model, as well as the lod and mip
+    /// level counts may differ, but the kind of the model and the storage of all
+    /// attachments have to match.
+    pub fn validate(&self, config: &TerrainConfig) -> Result<()> {
+        let path = &config.path;
+        let kind = |spherical| if spherical { "spherical" } else { "planar" };
+
+        if self.shape.is_spherical() != config.model.is_spherical() {
+            bail!(
+                "The terrain {path} was preprocessed as a {} terrain, but is configured as a {} terrain.",
+                kind(self.shape.is_spherical()),
+                kind(config.model.is_spherical())
+            );
+        }
+
+        if self.attachments.len() != config.attachments.len() {
+            bail!(
+                "The terrain {path} was preprocessed with {} attachments, but is configured with {}.",
+                self.attachments.len(),
+                config.attachments.len()
+            );
+        }
+
+        for (index, (stored, configured)) in
+            iter::zip(&self.attachments, &config.attachments).enumerate()
+        {
+            fn check<T: PartialEq + Debug>(
+                property: &str,
+                stored: &T,
+                configured: &T,
+                index: usize,
+                path: &str,
+            ) -> Result<()> {
+                if stored != configured {
+                    bail!("The attachment {index} of the terrain {path} was preprocessed with the {property} {stored:?}, but is configured with the {property} {configured:?}.");
+                }
+
+                Ok(())
+            }
+
+            check("name", &stored.name, &configured.name, index, path)?;
+            check(
+                "texture size",
+                &stored.texture_size,
+                &configured.texture_size,
+                index,
+                path,
+            )?;
+            check(
+                "border size",
+                &stored.border_size,
+                &configured.border_size,
+                index,
+                path,
+            )?;
+            check("format", &stored.format, &configured.format, index, path)?;
+            check("storage", &stored.storage, &configured.storage, index, path)?;
+            check(
+                "block compression",
+                &stored.block_compression,
+                &configured.block_compression,
+                index,
+                path,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Converts the stored tiles of an attachment into another storage format.
+    ///
+    /// This can be used to export the tiles as a PNG pyramid for inspecting them.
+    /// The previously stored tiles are kept, unless they are overwritten,
+    /// and the manifest has to be saved afterwards.
+    pub fn convert_storage(
+        &mut self,
+        source: &dyn TileSource,
+        attachment_index: usize,
+        storage: AttachmentStorage,
+    ) -> Result<()> {
+        let Some(attachment) = self.attachments.get_mut(attachment_index) else {
+            bail!("The terrain has no attachment {attachment_index}.");
+        };
+
+        if !storage.is_supported(attachment.format) {
+            bail!(
+                "The attachment {} with the {:?} format can not be stored as {storage:?}.",
+                attachment.name,
+                attachment.format
+            );
+        }
+
+        for &coordinate in &self.tiles {
+            let extension = attachment.storage.extension();
+            let data = block_on(source.load_tile(&attachment.name, coordinate, extension))?;
+            let data = AttachmentData::decode(
+                data,
+                attachment.storage,
+                attachment.format,
+                attachment.texture_size,
+            )?
+            .encode(storage, attachment.format, attachment.texture_size)?;
+
+            block_on(source.save_tile(&attachment.name, coordinate, storage.extension(), &data))?;
+        }
+
+        attachment.storage = storage;
+
+        Ok(())
+    }
+
     pub fn decode_alloc(encoded: &[u8]) -> Result<Self> {
         let config = config::standard();
-        let decoded = bincode::decode_from_slice(encoded, config)?;
+        let (version, read): (u32, usize) = bincode::decode_from_slice(encoded, config)?;
+
+        if version != MANIFEST_VERSION {
+            bail!("The terrain manifest has version {version}, but version {MANIFEST_VERSION} is required. Please preprocess the terrain again.");
+        }
+
+        let decoded = bincode::decode_from_slice(&encoded[read..], config)?;
         Ok(decoded.0)
     }
 
     pub fn encode_alloc(&self) -> Result<Vec<u8>> {
         let config = config::standard();
-        let encoded = bincode::encode_to_vec(self, config)?;
+        let mut encoded = bincode::encode_to_vec(MANIFEST_VERSION, config)?;
+        encoded.extend(bincode::encode_to_vec(self, config)?);
         Ok(encoded)
     }
 
@@ -1,3 +1,4 @@
+use crate::terrain_data::AttachmentFormat;
 use bevy::{
     asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
     prelude::*,
@@ -7,40 +8,295 @@ use bevy::{
         texture::TextureError,
     },
 };
-use bytemuck::cast_slice;
+use bytemuck::{cast_slice, Pod};
+use half::f16;
+use serde::{Deserialize, Serialize};
 use std::io::Cursor;
-use tiff::decoder::{Decoder, DecodingResult};
+use tiff::{
+    decoder::{Decoder, DecodingResult},
+    tags::Tag,
+    ColorType,
+};
+
+const PROJECTED_CS_TYPE_GEO_KEY: u16 = 3072;
+const GEOGRAPHIC_TYPE_GEO_KEY: u16 = 2048;
+
+fn invalid_data(error: impl ToString) -> TextureError {
+    TextureError::InvalidData(error.to_string())
+}
+
+/// The georeferencing information of a GeoTIFF.
+///
+/// It is available as the `metadata` labeled asset of each image loaded by the [`TiffLoader`].
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct GeoTiffMetadata {
+    pub width: u32,
+    pub height: u32,
+    /// The raster point (i, j, k) and the corresponding model point (x, y, z) of the first tie point.
+    pub tie_point: Option<[f64; 6]>,
+    /// The size of a pixel in model space (x, y, z).
+    pub pixel_scale: Option<[f64; 3]>,
+    /// The value of pixels without data.
+    pub nodata: Option<f64>,
+    /// The EPSG code of the projected or geographic coordinate system.
+    pub epsg: Option<u16>,
+}
+
+impl GeoTiffMetadata {
+    fn read<R: std::io::Read + std::io::Seek>(decoder: &mut Decoder<R>) -> Self {
+        let (width, height) = decoder.dimensions().unwrap_or_default();
+
+        let tie_point = decoder
+            .get_tag_f64_vec(Tag::ModelTiepointTag)
+            .ok()
+            .and_then(|values| values.get(0..6)?.try_into().ok());
+        let pixel_scale = decoder
+            .get_tag_f64_vec(Tag::ModelPixelScaleTag)
+            .ok()
+            .and_then(|values| values.get(0..3)?.try_into().ok());
+        let nodata = decoder
+            .get_tag_ascii_string(Tag::GdalNodata)
+            .ok()
+            .and_then(|value| value.trim_matches(char::from(0)).trim().parse().ok());
+
+        // The geo key directory consists of a header followed by entries of four values each:
+        // the key id, the tag location, the count and the value (if the location is zero).
+        let epsg = decoder
+            .get_tag_u16_vec(Tag::GeoKeyDirectoryTag)
+            .ok()
+            .and_then(|directory| {
+                let entries = directory.get(4..)?.chunks_exact(4);
+                let key = |id| {
+                    entries
+                        .clone()
+                        .find(|entry| entry[0] == id && entry[1] == 0)
+                        .map(|entry| entry[3])
+                };
+
+                key(PROJECTED_CS_TYPE_GEO_KEY).or_else(|| key(GEOGRAPHIC_TYPE_GEO_KEY))
+            });
+
+        Self {
+            width,
+            height,
+            tie_point,
+            pixel_scale,
+            nodata,
+            epsg,
+        }
+    }
+}
+
+/// The settings of the [`TiffLoader`].
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TiffLoaderSettings {
+    /// The attachment format the image is converted to.
+    /// Otherwise the image is loaded with the texture format matching its samples.
+    pub format: Option<AttachmentFormat>,
+    /// The range of values that is mapped to the normalized range of the format
+    /// (e.g. [-1, 1] for Snorm), when converting the samples.
+    /// Float formats ignore it and store the samples unchanged, since they hold heights in meters.
+    /// Otherwise integer samples are normalized by the range of their type
+    /// and floating point samples are left unchanged.
+    pub value_range: Option<(f32, f32)>,
+}
+
+/// Appends an opaque alpha channel to each pixel of an image with three channels.
+fn expand_rgb<T: Copy>(data: Vec<T>, alpha: T) -> Vec<T> {
+    data.chunks_exact(3)
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], alpha])
+        .collect()
+}
+
+fn native_image<T: Pod>(
+    data: Vec<T>,
+    channels: usize,
+    alpha: T,
+    formats: [TextureFormat; 3],
+) -> (Vec<u8>, TextureFormat) {
+    let (data, format) = match channels {
+        1 => (data, formats[0]),
+        2 => (data, formats[1]),
+        3 => (expand_rgb(data, alpha), formats[2]),
+        _ => (data, formats[2]),
+    };
+
+    (cast_slice(&data).to_vec(), format)
+}
+
+/// Converts the samples of the image into the processing format of the attachment.
+fn convert_image(
+    data: DecodingResult,
+    channels: usize,
+    format: AttachmentFormat,
+    settings: &TiffLoaderSettings,
+    nodata: Option<f64>,
+) -> Result<Vec<u8>, TextureError> {
+    fn values<T: Copy + Into<f64>>(data: Vec<T>, min: T, max: T) -> (Vec<f64>, f64, f64) {
+        (
+            data.into_iter().map(Into::into).collect(),
+            min.into(),
+            max.into(),
+        )
+    }
+
+    let (values, min, max) = match data {
+        DecodingResult::U8(data) => values(data, u8::MIN, u8::MAX),
+        DecodingResult::U16(data) => values(data, u16::MIN, u16::MAX),
+        DecodingResult::U32(data) => values(data, u32::MIN, u32::MAX),
+        DecodingResult::I8(data) => values(data, i8::MIN, i8::MAX),
+        DecodingResult::I16(data) => values(data, i16::MIN, i16::MAX),
+        DecodingResult::I32(data) => values(data, i32::MIN, i32::MAX),
+        DecodingResult::F32(data) => values(data, 0.0, 1.0),
+        DecodingResult::F64(data) => values(data, 0.0, 1.0),
+        DecodingResult::U64(_) | DecodingResult::I64(_) => {
+            return Err(TextureError::UnsupportedTextureFormat(
+                "64 bit integer samples can not be converted".to_string(),
+            ))
+        }
+    };
+
+    let (min, max) = match (format, settings.value_range) {
+        (AttachmentFormat::R32F | AttachmentFormat::R16F, Some(_)) => (0.0, 1.0),
+        (_, Some((min, max))) => (min as f64, max as f64),
+        (_, None) => (min, max),
+    };
+
+    let normalize = |value: f64| {
+        // nodata samples are mapped to the lowest value of the range
+        let value = if Some(value) == nodata { min } else { value };
+        let value = (value - min) / (max - min);
+
+        match format {
+            AttachmentFormat::R32F | AttachmentFormat::R16F => value,
+            AttachmentFormat::R16Snorm => (value * 2.0 - 1.0).clamp(-1.0, 1.0),
+            _ => value.clamp(0.0, 1.0),
+        }
+    };
+
+    let target_channels = match format {
+        AttachmentFormat::Rgb8 | AttachmentFormat::Rgba8 => 4,
+        AttachmentFormat::Rg16 => 2,
+        AttachmentFormat::R16
+        | AttachmentFormat::R32F
+        | AttachmentFormat::R16F
+        | AttachmentFormat::R16Snorm => 1,
+    };
+
+    let samples = values.chunks_exact(channels).flat_map(|pixel| {
+        (0..target_channels).map(move |channel| match pixel.get(channel) {
+            Some(&value) => normalize(value),
+            None if channel == 3 => 1.0,
+            None => 0.0,
+        })
+    });
+
+    Ok(match format {
+        AttachmentFormat::Rgb8 | AttachmentFormat::Rgba8 => samples
+            .map(|value| (value * u8::MAX as f64).round() as u8)
+            .collect(),
+        AttachmentFormat::R16 | AttachmentFormat::Rg16 => {
+            let samples = samples
+                .map(|value| (value * u16::MAX as f64).round() as u16)
+                .collect::<Vec<_>>();
+            cast_slice(&samples).to_vec()
+        }
+        AttachmentFormat::R32F => {
+            let samples = samples.map(|value| value as f32).collect::<Vec<_>>();
+            cast_slice(&samples).to_vec()
+        }
+        AttachmentFormat::R16F => {
+            let samples = samples.map(f16::from_f64).collect::<Vec<_>>();
+            cast_slice(&samples).to_vec()
+        }
+        AttachmentFormat::R16Snorm => {
+            let samples = samples
+                .map(|value| (value * i16::MAX as f64).round() as i16)
+                .collect::<Vec<_>>();
+            cast_slice(&samples).to_vec()
+        }
+    })
+}
 
 #[derive(Default)]
 pub struct TiffLoader;
 impl AssetLoader for TiffLoader {
     type Asset = Image;
-    type Settings = ();
+    type Settings = TiffLoaderSettings;
     type Error = TextureError;
     async fn load<'a>(
         &'a self,
         reader: &'a mut Reader<'_>,
-        _settings: &'a Self::Settings,
-        _load_context: &'a mut LoadContext<'_>,
+        settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext<'_>,
     ) -> Result<Image, Self::Error> {
         let mut bytes = Vec::new();
-        reader.read_to_end(&mut bytes).await.unwrap();
-
-        let mut decoder = Decoder::new(Cursor::new(bytes)).unwrap();
-
-        let (width, height) = decoder.dimensions().unwrap();
-
-        let data = match decoder.read_image().unwrap() {
-            DecodingResult::U8(data) => cast_slice(&data).to_vec(),
-            DecodingResult::U16(data) => cast_slice(&data).to_vec(),
-            DecodingResult::U32(data) => cast_slice(&data).to_vec(),
-            DecodingResult::U64(data) => cast_slice(&data).to_vec(),
-            DecodingResult::F32(data) => cast_slice(&data).to_vec(),
-            DecodingResult::F64(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I8(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I16(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I32(data) => cast_slice(&data).to_vec(),
-            DecodingResult::I64(data) => cast_slice(&data).to_vec(),
+        reader.read_to_end(&mut bytes).await.map_err(invalid_data)?;
+
+        let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(invalid_data)?;
+
+        let (width, height) = decoder.dimensions().map_err(invalid_data)?;
+
+        let channels = match decoder.colortype().map_err(invalid_data)? {
+            ColorType::Gray(_) => 1,
+            ColorType::GrayA(_) => 2,
+            ColorType::RGB(_) => 3,
+            ColorType::RGBA(_) => 4,
+            color_type => {
+                return Err(TextureError::UnsupportedTextureFormat(format!(
+                    "{color_type:?}"
+                )))
+            }
+        };
+
+        let metadata = GeoTiffMetadata::read(&mut decoder);
+        let nodata = metadata.nodata;
+        load_context.add_labeled_asset("metadata".to_string(), metadata);
+
+        let image = decoder.read_image().map_err(invalid_data)?;
+
+        let (data, format) = if let Some(format) = settings.format {
+            (
+                convert_image(image, channels, format, settings, nodata)?,
+                format.processing_format(),
+            )
+        } else {
+            use TextureFormat::*;
+
+            match image {
+                DecodingResult::U8(data) => {
+                    native_image(data, channels, u8::MAX, [R8Unorm, Rg8Unorm, Rgba8Unorm])
+                }
+                DecodingResult::U16(data) => {
+                    native_image(data, channels, u16::MAX, [R16Unorm, Rg16Unorm, Rgba16Unorm])
+                }
+                DecodingResult::U32(data) => {
+                    native_image(data, channels, u32::MAX, [R32Uint, Rg32Uint, Rgba32Uint])
+                }
+                DecodingResult::I8(data) => {
+                    native_image(data, channels, i8::MAX, [R8Snorm, Rg8Snorm, Rgba8Snorm])
+                }
+                DecodingResult::I16(data) => {
+                    native_image(data, channels, i16::MAX, [R16Snorm, Rg16Snorm, Rgba16Snorm])
+                }
+                DecodingResult::I32(data) => {
+                    native_image(data, channels, i32::MAX, [R32Sint, Rg32Sint, Rgba32Sint])
+                }
+                DecodingResult::F32(data) => {
+                    native_image(data, channels, 1.0, [R32Float, Rg32Float, Rgba32Float])
+                }
+                DecodingResult::F64(data) => native_image(
+                    data.into_iter().map(|value| value as f32).collect(),
+                    channels,
+                    1.0,
+                    [R32Float, Rg32Float, Rgba32Float],
+                ),
+                DecodingResult::U64(_) | DecodingResult::I64(_) => {
+                    return Err(TextureError::UnsupportedTextureFormat(
+                        "64 bit integer samples are not supported".to_string(),
+                    ))
+                }
+            }
         };
 
         Ok(Image::new(
@@ -51,7 +307,7 @@ impl AssetLoader for TiffLoader {
             },
             TextureDimension::D2,
             data,
-            TextureFormat::R16Unorm,
+            format,
             RenderAssetUsages::default(),
         ))
     }
@@ -60,3 +316,58 @@ impl AssetLoader for TiffLoader {
         &["tif", "tiff"]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain_data::AttachmentData;
+    use tiff::encoder::{colortype::Gray32Float, TiffEncoder};
+
+    #[test]
+    fn float_heights_round_trip() {
+        let (min_height, max_height) = (-120.0, 2400.0);
+        let heights = [
+            -120.0, -60.5, 0.0, 37.25, 450.0, 812.5, 1200.0, 1999.75, 2400.0,
+        ];
+
+        let mut bytes = Vec::new();
+        TiffEncoder::new(Cursor::new(&mut bytes))
+            .unwrap()
+            .write_image::<Gray32Float>(3, 3, &heights)
+            .unwrap();
+
+        for format in [
+            AttachmentFormat::R16,
+            AttachmentFormat::R16Snorm,
+            AttachmentFormat::R16F,
+            AttachmentFormat::R32F,
+        ] {
+            let mut decoder = Decoder::new(Cursor::new(&bytes)).unwrap();
+            let image = decoder.read_image().unwrap();
+
+            let settings = TiffLoaderSettings {
+                format: Some(format),
+                value_range: Some((min_height, max_height)),
+            };
+
+            let data = convert_image(image, 1, format, &settings, None).unwrap();
+            let data = AttachmentData::from_bytes(&data, format, 3).unwrap();
+            let (offset, scale) = format.height_transform(min_height, max_height);
+
+            // one quantization step of the format
+            let tolerance = match format {
+                AttachmentFormat::R16F => max_height / 1024.0,
+                _ => (max_height - min_height) / i16::MAX as f32,
+            };
+
+            for (index, &height) in heights.iter().enumerate() {
+                let decoded = offset + scale * data.pixel(index).x;
+
+                assert!(
+                    (decoded - height).abs() <= tolerance,
+                    "{height} was decoded as {decoded} with the {format:?} format"
+                );
+            }
+        }
+    }
+}
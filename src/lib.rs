@@ -64,23 +64,40 @@ pub mod prelude {
     #[cfg(feature = "high_precision")]
     pub use crate::big_space::{BigSpaceCommands, ReferenceFrame};
 
+    #[cfg(feature = "http")]
+    pub use crate::terrain_data::http_tile_source::HttpTileSource;
+
     pub use crate::{
         debug::{
             camera::{DebugCameraBundle, DebugCameraController},
             DebugTerrainMaterial, LoadingImages, TerrainDebugPlugin,
         },
         math::TerrainModel,
-        plugin::TerrainPlugin,
+        plugin::{TerrainDataPlugin, TerrainPlugin},
         preprocess::{
             preprocessor::Preprocessor,
             preprocessor::{PreprocessDataset, SphericalDataset},
             TerrainPreprocessPlugin,
         },
         render::terrain_material::TerrainMaterialPlugin,
-        terrain::{TerrainBundle, TerrainConfig},
+        terrain::{AttachmentFootprint, MemoryBudget, TerrainBundle, TerrainConfig},
         terrain_data::{
-            tile_atlas::TileAtlas, tile_tree::TileTree, AttachmentConfig, AttachmentFormat,
+            block_compression::BlockCompression,
+            request_policy::{
+                AltitudePolicy, DistancePolicy, FrustumPolicy, RequestContext, RequestPolicy,
+                ScreenSizePolicy, TileRequest,
+            },
+            statistics::{LatencyHistogram, TileAtlasStatistics},
+            tile_atlas::{
+                StreamingSettings, TileAtlas, TileAtlasExhausted, TileEvent, TileEventKind,
+                TileLoadError, TilePin,
+            },
+            tile_source::{ArchiveTileSource, FileTileSource, TileLayout, TileSource},
+            tile_tree::TileTree,
+            AttachmentConfig, AttachmentFormat, AttachmentStorage,
+        },
+        terrain_view::{
+            TerrainProbe, TerrainProbeConfig, TerrainView, TerrainViewComponents, TerrainViewConfig,
         },
-        terrain_view::{TerrainViewComponents, TerrainViewConfig},
     };
 }
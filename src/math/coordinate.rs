@@ -181,6 +181,20 @@ impl TileCoordinate {
         1 << lod
     }
 
+    /// Parses a tile coordinate from its string representation (`side_lod_x_y`).
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split('_').map(|part| part.parse().ok());
+
+        let coordinate = Self::new(
+            parts.next()??,
+            parts.next()??,
+            parts.next()??,
+            parts.next()??,
+        );
+
+        parts.next().is_none().then_some(coordinate)
+    }
+
     pub fn path(self, path: &str, extension: &str) -> String {
         format!("{path}/{self}.{extension}")
     }
@@ -2,6 +2,7 @@ mod coordinate;
 mod ellipsoid;
 mod terrain_model;
 
+pub(crate) use crate::math::terrain_model::TerrainKind;
 pub use crate::math::{
     coordinate::{Coordinate, TileCoordinate},
     terrain_model::{
@@ -42,7 +42,7 @@ pub struct TerrainModel {
     pub(crate) kind: TerrainKind,
     pub(crate) min_height: f32,
     pub(crate) max_height: f32,
-    translation: DVec3,
+    pub(crate) translation: DVec3,
     scale: DVec3,
     rotation: DQuat,
     world_from_local: DMat4,
@@ -365,7 +365,13 @@ pub fn generate_terrain_model_approximation(
     tile_atlases: Query<&TileAtlas>,
     mut terrain_model_approximations: ResMut<TerrainViewComponents<TerrainModelApproximation>>,
 ) {
+    terrain_model_approximations.retain(|key, _| tile_trees.contains_key(key));
+
     for (&(terrain, view), tile_tree) in tile_trees.iter() {
+        if !tile_tree.rendered {
+            continue;
+        }
+
         let tile_atlas = tile_atlases.get(terrain).unwrap();
 
         terrain_model_approximations.insert(
@@ -12,10 +12,12 @@ use crate::{
     shaders::{load_terrain_shaders, InternalShaders},
     terrain::TerrainComponents,
     terrain_data::{
-        gpu_tile_atlas::GpuTileAtlas, gpu_tile_tree::GpuTileTree, tile_atlas::TileAtlas,
+        gpu_tile_atlas::GpuTileAtlas,
+        gpu_tile_tree::GpuTileTree,
+        tile_atlas::{TileAtlas, TileAtlasExhausted, TileEvent, TileLoadError},
         tile_tree::TileTree,
     },
-    terrain_view::TerrainViewComponents,
+    terrain_view::{TerrainProbe, TerrainView, TerrainViewComponents},
 };
 use bevy::{
     prelude::*,
@@ -28,32 +30,68 @@ use bevy::{
     },
 };
 
-/// The plugin for the terrain renderer.
-pub struct TerrainPlugin;
+/// The plugin for the terrain data, which streams the tiles of the [`TileAtlas`]es
+/// according to their [`TileTree`]s.
+///
+/// It does not depend on the renderer, so it can be used with the `MinimalPlugins`
+/// (e.g. on dedicated servers) to sample the terrain on the CPU.
+/// Without a render app, the loaded tiles are not uploaded to the GPU.
+/// This plugin is part of the [`TerrainPlugin`].
+pub struct TerrainDataPlugin;
 
-impl Plugin for TerrainPlugin {
+impl Plugin for TerrainDataPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "high_precision")]
         app.add_plugins(crate::big_space::BigSpacePlugin::default());
 
-        app.init_resource::<InternalShaders>()
-            .init_resource::<TerrainViewComponents<TileTree>>()
-            .init_resource::<TerrainViewComponents<TerrainModelApproximation>>()
-            .add_systems(
-                PostUpdate,
-                check_visibility::<With<TileAtlas>>.in_set(VisibilitySystems::CheckVisibility),
-            )
+        app.init_resource::<TerrainViewComponents<TileTree>>()
+            .add_event::<TileAtlasExhausted>()
+            .add_event::<TileEvent>()
+            .add_event::<TileLoadError>()
             .add_systems(
                 Last,
                 (
+                    TileAtlas::check_device_features,
+                    TileAtlas::clamp_atlas_size,
+                    TileTree::remove_despawned,
+                    TerrainView::create_tile_trees,
+                    TerrainProbe::create_tile_trees,
                     TileTree::compute_requests,
                     TileAtlas::update,
                     TileTree::adjust_to_tile_atlas,
                     TileTree::approximate_height,
-                    generate_terrain_model_approximation,
                 )
                     .chain(),
             );
+    }
+
+    fn finish(&self, app: &mut App) {
+        // the render app exists once all plugins are built, independent of their order
+        if app.get_sub_app(RenderApp).is_none() {
+            app.add_systems(Last, TileAtlas::discard_uploads.after(TileAtlas::update));
+        }
+    }
+}
+
+/// The plugin for the terrain renderer.
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<TerrainDataPlugin>() {
+            app.add_plugins(TerrainDataPlugin);
+        }
+
+        app.init_resource::<InternalShaders>()
+            .init_resource::<TerrainViewComponents<TerrainModelApproximation>>()
+            .add_systems(
+                PostUpdate,
+                check_visibility::<With<TileAtlas>>.in_set(VisibilitySystems::CheckVisibility),
+            )
+            .add_systems(
+                Last,
+                generate_terrain_model_approximation.after(TileTree::approximate_height),
+            );
 
         app.sub_app_mut(RenderApp)
             .init_resource::<TerrainComponents<GpuTileAtlas>>()
@@ -65,6 +103,12 @@ impl Plugin for TerrainPlugin {
             .add_systems(
                 ExtractSchedule,
                 (
+                    TerrainComponents::<GpuTileAtlas>::remove_despawned,
+                    TerrainComponents::<TerrainData>::remove_despawned,
+                    TerrainViewComponents::<GpuTileTree>::remove_despawned,
+                    TerrainViewComponents::<TerrainViewData>::remove_despawned,
+                    TerrainViewComponents::<CullingBindGroup>::remove_despawned,
+                    TerrainViewComponents::<TilingPrepassItem>::remove_despawned,
                     GpuTileAtlas::initialize,
                     GpuTileAtlas::extract.after(GpuTileAtlas::initialize),
                     GpuTileTree::initialize,
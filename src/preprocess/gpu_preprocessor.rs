@@ -104,7 +104,7 @@ impl GpuPreprocessor {
 
     pub(crate) fn extract(
         mut gpu_preprocessors: ResMut<TerrainComponents<GpuPreprocessor>>,
-        preprocessors: Extract<Query<(Entity, &Preprocessor)>>,
+        preprocessors: Extract<Query<(Entity, &Preprocessor), With<TileAtlas>>>,
     ) {
         for (terrain, preprocessor) in preprocessors.iter() {
             let gpu_preprocessor = gpu_preprocessors.get_mut(&terrain).unwrap();
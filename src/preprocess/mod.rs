@@ -1,14 +1,19 @@
 use crate::{
-    formats::tiff::TiffLoader,
+    formats::tiff::{GeoTiffMetadata, TiffLoader},
     preprocess::{
         gpu_preprocessor::{
             create_downsample_layout, create_split_layout, create_stitch_layout, GpuPreprocessor,
         },
-        preprocessor::{preprocessor_load_tile, select_ready_tasks, PreprocessTaskType},
+        preprocessor::{
+            mark_preprocessing, preprocessor_load_tile, select_ready_tasks, PreprocessTaskType,
+        },
     },
     shaders::{load_preprocess_shaders, DOWNSAMPLE_SHADER, SPLIT_SHADER, STITCH_SHADER},
     terrain::TerrainComponents,
-    terrain_data::gpu_tile_atlas::{create_attachment_layout, GpuTileAtlas},
+    terrain_data::{
+        gpu_tile_atlas::{create_attachment_layout, GpuTileAtlas},
+        tile_atlas::TileAtlas,
+    },
 };
 use bevy::{
     prelude::*,
@@ -256,8 +261,13 @@ pub struct TerrainPreprocessPlugin;
 
 impl Plugin for TerrainPreprocessPlugin {
     fn build(&self, app: &mut App) {
-        app.init_asset_loader::<TiffLoader>()
-            .add_systems(Update, (select_ready_tasks, preprocessor_load_tile));
+        app.init_asset::<GeoTiffMetadata>()
+            .init_asset_loader::<TiffLoader>()
+            .add_systems(Update, (select_ready_tasks, preprocessor_load_tile))
+            .add_systems(
+                Last,
+                mark_preprocessing.before(TileAtlas::check_device_features),
+            );
 
         app.sub_app_mut(RenderApp)
             .init_resource::<TerrainComponents<GpuPreprocessor>>()
@@ -265,6 +275,8 @@ impl Plugin for TerrainPreprocessPlugin {
             .add_systems(
                 ExtractSchedule,
                 (
+                    TerrainComponents::<GpuPreprocessor>::remove_despawned,
+                    TerrainComponents::<TerrainPreprocessItem>::remove_despawned,
                     GpuPreprocessor::initialize,
                     GpuPreprocessor::extract.after(GpuPreprocessor::initialize),
                 ),
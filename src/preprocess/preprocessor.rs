@@ -1,7 +1,9 @@
 use crate::{
+    formats::tiff::TiffLoaderSettings,
     math::TileCoordinate,
     terrain_data::{
         tile_atlas::{AtlasTile, AtlasTileAttachment, TileAtlas},
+        tile_source::ArchiveTileSource,
         AttachmentFormat,
     },
     util::CollectArray,
@@ -16,7 +18,8 @@ use std::{
 };
 
 pub fn reset_directory(directory: &str) {
-    let _ = fs::remove_file(format!("{directory}/../../config.tc"));
+    let _ = fs::remove_file(format!("{directory}/../../manifest.tm"));
+    let _ = fs::remove_file(ArchiveTileSource::archive_path(directory));
     let _ = fs::remove_dir_all(directory);
     fs::create_dir_all(directory).unwrap();
 }
@@ -99,9 +102,7 @@ impl PreprocessTask {
             }
             PreprocessTaskType::Stitch { .. } => true,
             PreprocessTaskType::Downsample { .. } => true,
-            PreprocessTaskType::Barrier => {
-                tile_atlas.state.download_slots == tile_atlas.state.max_download_slots
-            }
+            PreprocessTaskType::Barrier => tile_atlas.state.downloading == 0,
             PreprocessTaskType::Save => true,
         }
     }
@@ -237,11 +238,27 @@ impl Preprocessor {
         asset_server: &AssetServer,
         tile_atlas: &mut TileAtlas,
     ) {
-        let tile_handle = asset_server.load(&dataset.path);
+        let format = tile_atlas.attachments[dataset.attachment_index as usize].format;
+
+        // the heights are stored relative to the height range of the terrain
+        let value_range = (dataset.attachment_index == 0)
+            .then_some((tile_atlas.model.min_height, tile_atlas.model.max_height));
+
+        let tile_handle = if dataset.path.ends_with(".tif") || dataset.path.ends_with(".tiff") {
+            asset_server.load_with_settings(
+                &dataset.path,
+                move |settings: &mut TiffLoaderSettings| {
+                    settings.format = Some(format);
+                    settings.value_range = value_range;
+                },
+            )
+        } else {
+            asset_server.load(&dataset.path)
+        };
 
         self.loading_tiles.push(LoadingTile {
             id: tile_handle.id(),
-            format: tile_atlas.attachments[dataset.attachment_index as usize].format,
+            format,
         });
 
         let mut lods = dataset.lod_range.clone().rev();
@@ -343,6 +360,13 @@ impl Preprocessor {
     }
 }
 
+/// Marks the tile atlases of newly preprocessed terrains, which keeps their GPU atlas uncompressed.
+pub(crate) fn mark_preprocessing(mut tile_atlases: Query<&mut TileAtlas, Added<Preprocessor>>) {
+    for mut tile_atlas in &mut tile_atlases {
+        tile_atlas.preprocessing = true;
+    }
+}
+
 pub(crate) fn select_ready_tasks(
     asset_server: Res<AssetServer>,
     mut terrains: Query<(&mut Preprocessor, &mut TileAtlas)>,
@@ -357,8 +381,8 @@ pub(crate) fn select_ready_tasks(
 
         if let Some(time) = start_time {
             if task_queue.is_empty()
-                && tile_atlas.state.download_slots == tile_atlas.state.max_download_slots
-                && tile_atlas.state.save_slots == tile_atlas.state.max_save_slots
+                && tile_atlas.state.downloading == 0
+                && tile_atlas.state.saving == 0
             {
                 println!("Preprocessing took {:?}", time.elapsed());
 
@@ -376,7 +400,7 @@ pub(crate) fn select_ready_tasks(
         ready_tasks.clear();
 
         loop {
-            if (tile_atlas.state.download_slots > 0)
+            if (tile_atlas.state.downloading < tile_atlas.state.settings.download_slots)
                 && task_queue
                     .front()
                     .map_or(false, |task| task.is_ready(&asset_server, &tile_atlas))
@@ -389,7 +413,7 @@ pub(crate) fn select_ready_tasks(
                     tile_atlas.save(task.tile);
                 } else {
                     ready_tasks.push(task);
-                    tile_atlas.state.download_slots -= 1;
+                    tile_atlas.state.downloading += 1;
                 }
             } else {
                 break;
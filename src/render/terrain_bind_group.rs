@@ -79,15 +79,21 @@ struct TerrainConfigUniform {
     min_height: f32,
     max_height: f32,
     scale: f32,
+    height_offset: f32,
+    height_scale: f32,
 }
 
 impl TerrainConfigUniform {
     fn from_tile_atlas(tile_atlas: &TileAtlas) -> Self {
+        let (height_offset, height_scale) = tile_atlas.height_transform();
+
         Self {
             lod_count: tile_atlas.lod_count,
             min_height: tile_atlas.model.min_height,
             max_height: tile_atlas.model.max_height,
             scale: tile_atlas.model.scale() as f32,
+            height_offset,
+            height_scale,
         }
     }
 }
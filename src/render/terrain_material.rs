@@ -391,7 +391,11 @@ pub(crate) fn queue_terrain<M: Material>(
         let draw_function = draw_functions.read().get_id::<DrawTerrain<M>>().unwrap();
 
         for (&terrain, &material_id) in render_material_instances.iter() {
-            let gpu_tile_atlas = gpu_tile_atlases.get(&terrain).unwrap();
+            // terrains, which the render device can not sample, are not rendered
+            let Some(gpu_tile_atlas) = gpu_tile_atlases.get(&terrain) else {
+                continue;
+            };
+
             if let Some(material) = render_materials.get(material_id) {
                 let mut flags = TerrainPipelineFlags::from_msaa_samples(msaa.samples());
 
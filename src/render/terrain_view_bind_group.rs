@@ -197,8 +197,8 @@ impl TerrainViewData {
         tile_trees: Extract<Res<TerrainViewComponents<TileTree>>>,
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
-            if terrain_view_data.contains_key(&(terrain, view)) {
-                return;
+            if !tile_tree.rendered || terrain_view_data.contains_key(&(terrain, view)) {
+                continue;
             }
 
             let gpu_tile_tree = gpu_tile_trees.get(&(terrain, view)).unwrap();
@@ -218,7 +218,9 @@ impl TerrainViewData {
         >,
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
-            let terrain_view_data = terrain_view_data.get_mut(&(terrain, view)).unwrap();
+            let Some(terrain_view_data) = terrain_view_data.get_mut(&(terrain, view)) else {
+                continue;
+            };
 
             terrain_view_data
                 .view_config_buffer
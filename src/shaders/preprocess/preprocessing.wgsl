@@ -3,6 +3,10 @@
 const FORMAT_R8: u32 = 2u;
 const FORMAT_RGBA8: u32 = 0u;
 const FORMAT_R16: u32 = 1u;
+const FORMAT_RGB8: u32 = 5u;
+const FORMAT_R32F: u32 = 6u;
+const FORMAT_R16F: u32 = 7u;
+const FORMAT_R16SNORM: u32 = 8u;
 
 const INVALID_ATLAS_INDEX: u32 = 4294967295u;
 
@@ -78,7 +82,7 @@ fn process_entry(entry_coords: vec3<u32>) {
                                                  pixel_value(pixel_coords(entry_coords, 3u)).x));
         store_entry(entry_coords, entry_value);
     }
-    if (attachment.format_id == FORMAT_RGBA8) {
+    if (attachment.format_id == FORMAT_RGBA8 || attachment.format_id == FORMAT_RGB8) {
         let entry_value = pack4x8unorm(pixel_value(pixel_coords(entry_coords, 0u)));
         store_entry(entry_coords, entry_value);
     }
@@ -87,4 +91,18 @@ fn process_entry(entry_coords: vec3<u32>) {
                                               pixel_value(pixel_coords(entry_coords, 1u)).x));
         store_entry(entry_coords, entry_value);
     }
+    if (attachment.format_id == FORMAT_R32F) {
+        let entry_value = bitcast<u32>(pixel_value(pixel_coords(entry_coords, 0u)).x);
+        store_entry(entry_coords, entry_value);
+    }
+    if (attachment.format_id == FORMAT_R16F) {
+        let entry_value = pack2x16float(vec2<f32>(pixel_value(pixel_coords(entry_coords, 0u)).x,
+                                                  pixel_value(pixel_coords(entry_coords, 1u)).x));
+        store_entry(entry_coords, entry_value);
+    }
+    if (attachment.format_id == FORMAT_R16SNORM) {
+        let entry_value = pack2x16snorm(vec2<f32>(pixel_value(pixel_coords(entry_coords, 0u)).x,
+                                                  pixel_value(pixel_coords(entry_coords, 1u)).x));
+        store_entry(entry_coords, entry_value);
+    }
 }
@@ -5,6 +5,8 @@ struct TerrainConfig {
     min_height: f32,
     max_height: f32,
     scale: f32,
+    height_offset: f32,
+    height_scale: f32,
 }
 
 struct TerrainViewConfig {
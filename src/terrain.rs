@@ -4,10 +4,22 @@
 use crate::big_space::{GridCell, GridTransformOwned, ReferenceFrame};
 
 use crate::{
+    formats::TerrainManifest,
     math::TerrainModel,
-    terrain_data::{tile_atlas::TileAtlas, AttachmentConfig},
+    terrain_data::{
+        statistics::TileAtlasStatistics,
+        tile_atlas::{StreamingSettings, TileAtlas},
+        tile_source::{TileLayout, TileSource},
+        AttachmentConfig,
+    },
 };
-use bevy::{ecs::entity::EntityHashMap, prelude::*, render::view::NoFrustumCulling};
+use anyhow::Result;
+use bevy::{
+    ecs::entity::EntityHashMap,
+    prelude::*,
+    render::{view::NoFrustumCulling, Extract},
+};
+use std::sync::Arc;
 
 /// Resource that stores components that are associated to a terrain entity.
 /// This is used to persist components in the render world.
@@ -20,6 +32,48 @@ impl<C> Default for TerrainComponents<C> {
     }
 }
 
+impl<C: Send + Sync + 'static> TerrainComponents<C> {
+    /// Removes the components of despawned terrains.
+    pub(crate) fn remove_despawned(
+        mut components: ResMut<Self>,
+        tile_atlases: Extract<Query<(), With<TileAtlas>>>,
+    ) {
+        components.retain(|&terrain, _| tile_atlases.contains(terrain));
+    }
+}
+
+/// A budget for the memory occupied by the tile atlas of a terrain.
+///
+/// The capacity of the atlas is derived from the budget and the tile sizes of all attachments.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryBudget {
+    /// The maximum amount of bytes the tile data occupies in main memory.
+    pub cpu: Option<u64>,
+    /// The maximum amount of bytes the atlas textures occupy in video memory.
+    pub gpu: Option<u64>,
+}
+
+/// The memory occupied by an attachment of a tile atlas.
+#[derive(Clone, Debug)]
+pub struct AttachmentFootprint {
+    /// The name of the attachment.
+    pub name: String,
+    /// The amount of bytes the tile data occupies in main memory, once the atlas is full.
+    pub cpu_bytes: u64,
+    /// The amount of bytes the atlas texture occupies in video memory.
+    pub gpu_bytes: u64,
+}
+
+impl AttachmentFootprint {
+    pub(crate) fn new(attachment: &AttachmentConfig, atlas_size: u32) -> Self {
+        Self {
+            name: attachment.name.clone(),
+            cpu_bytes: attachment.cpu_tile_size() * atlas_size as u64,
+            gpu_bytes: attachment.gpu_tile_size() * atlas_size as u64,
+        }
+    }
+}
+
 /// The configuration of a terrain.
 ///
 /// Here you can define all fundamental parameters of the terrain.
@@ -29,11 +83,22 @@ pub struct TerrainConfig {
     pub lod_count: u32,
     pub model: TerrainModel,
     /// The amount of tiles the can be loaded simultaneously in the tile atlas.
+    /// This is ignored, if a memory budget is specified.
     pub atlas_size: u32,
+    /// The memory budget the capacity of the tile atlas is derived from.
+    pub memory_budget: MemoryBudget,
     /// The path to the terrain folder inside the assets directory.
     pub path: String,
     /// The attachments of the terrain.
     pub attachments: Vec<AttachmentConfig>,
+    /// The on-disk layout of the tiles inside the terrain folder.
+    /// This is used to create the tile source, if none is specified.
+    pub tile_layout: TileLayout,
+    /// The source the tiles of the terrain are loaded from and saved to.
+    /// Defaults to the source of the `tile_layout`.
+    pub tile_source: Option<Arc<dyn TileSource>>,
+    /// The limits of the concurrent loading, saving and uploading of tiles.
+    pub streaming: StreamingSettings,
 }
 
 impl Default for TerrainConfig {
@@ -42,17 +107,80 @@ impl Default for TerrainConfig {
             lod_count: 1,
             model: TerrainModel::sphere(default(), 1.0, 0.0, 1.0),
             atlas_size: 1024,
+            memory_budget: default(),
             path: default(),
             attachments: default(),
+            tile_layout: default(),
+            tile_source: None,
+            streaming: default(),
         }
     }
 }
 
 impl TerrainConfig {
+    /// Creates the config of a preprocessed terrain from the manifest inside its terrain folder.
+    pub fn load_from_manifest(path: &str) -> Result<Self> {
+        let manifest = TerrainManifest::load_file(TerrainManifest::path(path))?;
+
+        Ok(Self {
+            lod_count: manifest.lod_count,
+            model: manifest.model(),
+            path: path.to_string(),
+            attachments: manifest.attachments,
+            ..default()
+        })
+    }
+
+    /// Derives the capacity of the tile atlas from the memory budget.
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+
+    /// The amount of tiles the tile atlas can hold, which is either derived from the memory budget
+    /// or the `atlas_size`.
+    ///
+    /// Once the terrain is spawned, this is limited to the maximum amount of texture array layers
+    /// supported by the render device.
+    pub fn effective_atlas_size(&self) -> u32 {
+        let capacity = |budget: Option<u64>, tile_size: fn(&AttachmentConfig) -> u64| {
+            let tile_size: u64 = self.attachments.iter().map(tile_size).sum();
+            budget.map(|budget| budget / tile_size.max(1))
+        };
+
+        let cpu_capacity = capacity(self.memory_budget.cpu, AttachmentConfig::cpu_tile_size);
+        let gpu_capacity = capacity(self.memory_budget.gpu, AttachmentConfig::gpu_tile_size);
+
+        match cpu_capacity.into_iter().chain(gpu_capacity).min() {
+            Some(capacity) => capacity.clamp(1, u32::MAX as u64) as u32,
+            None => self.atlas_size,
+        }
+    }
+
+    /// The memory the attachments of the tile atlas occupy.
+    pub fn memory_footprint(&self) -> Vec<AttachmentFootprint> {
+        let atlas_size = self.effective_atlas_size();
+
+        self.attachments
+            .iter()
+            .map(|attachment| AttachmentFootprint::new(attachment, atlas_size))
+            .collect()
+    }
+
     pub fn add_attachment(mut self, attachment_config: AttachmentConfig) -> Self {
         self.attachments.push(attachment_config);
         self
     }
+
+    pub fn with_streaming_settings(mut self, streaming: StreamingSettings) -> Self {
+        self.streaming = streaming;
+        self
+    }
+
+    pub fn with_tile_source(mut self, tile_source: impl TileSource) -> Self {
+        self.tile_source = Some(Arc::new(tile_source));
+        self
+    }
 }
 
 /// The components of a terrain.
@@ -61,6 +189,7 @@ impl TerrainConfig {
 #[derive(Bundle)]
 pub struct TerrainBundle {
     pub tile_atlas: TileAtlas,
+    pub statistics: TileAtlasStatistics,
     #[cfg(feature = "high_precision")]
     pub cell: GridCell,
     pub transform: Transform,
@@ -83,6 +212,7 @@ impl TerrainBundle {
 
         Self {
             tile_atlas,
+            statistics: default(),
             transform,
             #[cfg(feature = "high_precision")]
             cell,
@@ -0,0 +1,429 @@
+use crate::terrain_data::{AttachmentData, AttachmentFormat};
+use bevy::{math::Vec4, render::render_resource::*};
+use bincode::{Decode, Encode};
+use itertools::iproduct;
+
+/// The 4 bit interpolation weights of BC7.
+const BC7_WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// The block compression of an attachment inside the GPU atlas.
+///
+/// Block compressed attachments occupy a fraction of the video memory of uncompressed ones.
+/// The preprocessor processes the tiles uncompressed and stores the encoded blocks of all
+/// mip levels alongside them, which are then uploaded to the GPU as is.
+/// The height attachment can not be block compressed, and rendering block compressed attachments
+/// requires the [`WgpuFeatures::TEXTURE_COMPRESSION_BC`] feature of the render device.
+#[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlockCompression {
+    /// The attachment is stored uncompressed.
+    #[default]
+    None,
+    /// Three color channels with 4 bits per pixel. Supports [`AttachmentFormat::Rgb8`] and [`AttachmentFormat::Rgba8`].
+    Bc1,
+    /// One channel with 4 bits per pixel. Supports [`AttachmentFormat::R16`].
+    Bc4,
+    /// Two channels with 8 bits per pixel. Supports [`AttachmentFormat::Rg16`].
+    Bc5,
+    /// Four channels with 8 bits per pixel. Supports [`AttachmentFormat::Rgb8`] and [`AttachmentFormat::Rgba8`].
+    Bc7,
+}
+
+impl BlockCompression {
+    pub(crate) fn is_supported(self, format: AttachmentFormat) -> bool {
+        match self {
+            BlockCompression::None => true,
+            BlockCompression::Bc1 | BlockCompression::Bc7 => {
+                matches!(format, AttachmentFormat::Rgb8 | AttachmentFormat::Rgba8)
+            }
+            BlockCompression::Bc4 => matches!(format, AttachmentFormat::R16),
+            BlockCompression::Bc5 => matches!(format, AttachmentFormat::Rg16),
+        }
+    }
+
+    pub(crate) fn required_features(self) -> WgpuFeatures {
+        match self {
+            BlockCompression::None => WgpuFeatures::empty(),
+            _ => WgpuFeatures::TEXTURE_COMPRESSION_BC,
+        }
+    }
+
+    pub(crate) fn render_format(self, format: AttachmentFormat) -> TextureFormat {
+        match self {
+            BlockCompression::None => format.render_format(),
+            BlockCompression::Bc1 => TextureFormat::Bc1RgbaUnormSrgb,
+            BlockCompression::Bc4 => TextureFormat::Bc4RUnorm,
+            BlockCompression::Bc5 => TextureFormat::Bc5RgUnorm,
+            BlockCompression::Bc7 => TextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    pub(crate) fn processing_format(self, format: AttachmentFormat) -> TextureFormat {
+        match self {
+            BlockCompression::None => format.processing_format(),
+            BlockCompression::Bc1 => TextureFormat::Bc1RgbaUnorm,
+            BlockCompression::Bc4 => TextureFormat::Bc4RUnorm,
+            BlockCompression::Bc5 => TextureFormat::Bc5RgUnorm,
+            BlockCompression::Bc7 => TextureFormat::Bc7RgbaUnorm,
+        }
+    }
+
+    /// The file extension of the stored blocks.
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            BlockCompression::None => "",
+            BlockCompression::Bc1 => "bc1",
+            BlockCompression::Bc4 => "bc4",
+            BlockCompression::Bc5 => "bc5",
+            BlockCompression::Bc7 => "bc7",
+        }
+    }
+
+    /// The size of a 4x4 block in bytes.
+    pub(crate) fn block_size(self) -> u32 {
+        match self {
+            BlockCompression::None => 0,
+            BlockCompression::Bc1 | BlockCompression::Bc4 => 8,
+            BlockCompression::Bc5 | BlockCompression::Bc7 => 16,
+        }
+    }
+
+    /// The count of blocks per side of the mip level.
+    /// Mip levels smaller than a block are padded to a full block.
+    pub(crate) fn blocks_per_side(texture_size: u32, mip_level: u32) -> u32 {
+        (texture_size >> mip_level).max(1).div_ceil(4)
+    }
+
+    /// Encodes all mip levels of the attachment data into blocks.
+    pub(crate) fn encode(
+        self,
+        data: &AttachmentData,
+        texture_size: u32,
+        mip_level_count: u32,
+    ) -> Option<Vec<u8>> {
+        if self == BlockCompression::None {
+            return None;
+        }
+
+        let mut blocks = Vec::new();
+        let mut start = 0;
+
+        for mip_level in 0..mip_level_count {
+            let size = (texture_size >> mip_level).max(1) as usize;
+            let blocks_per_side = Self::blocks_per_side(texture_size, mip_level) as usize;
+
+            for (block_y, block_x) in iproduct!(0..blocks_per_side, 0..blocks_per_side) {
+                let pixels: [Vec4; 16] = std::array::from_fn(|i| {
+                    // pixels outside of the mip level are clamped to its edge
+                    let x = (block_x * 4 + i % 4).min(size - 1);
+                    let y = (block_y * 4 + i / 4).min(size - 1);
+
+                    data.pixel(start + y * size + x)
+                });
+
+                match self {
+                    BlockCompression::None => unreachable!(),
+                    BlockCompression::Bc1 => blocks.extend(encode_bc1(&pixels)),
+                    BlockCompression::Bc4 => blocks.extend(encode_bc4(&pixels, 0)),
+                    BlockCompression::Bc5 => {
+                        blocks.extend(encode_bc4(&pixels, 0));
+                        blocks.extend(encode_bc4(&pixels, 1));
+                    }
+                    BlockCompression::Bc7 => blocks.extend(encode_bc7(&pixels)),
+                }
+            }
+
+            start += size * size;
+        }
+
+        Some(blocks)
+    }
+}
+
+fn quantize(value: f32, max: u32) -> u32 {
+    (value.clamp(0.0, 1.0) * max as f32).round() as u32
+}
+
+/// Returns the index of the palette entry closest to the value.
+fn closest<const N: usize>(palette: &[Vec4; N], value: Vec4) -> u32 {
+    (0..N)
+        .min_by(|&a, &b| {
+            let a = palette[a].distance_squared(value);
+            let b = palette[b].distance_squared(value);
+            a.total_cmp(&b)
+        })
+        .unwrap() as u32
+}
+
+/// Encodes the color of the block with the opaque four color mode of BC1.
+fn encode_bc1(pixels: &[Vec4; 16]) -> [u8; 8] {
+    let to_565 = |color: Vec4| {
+        (quantize(color.x, 31) << 11 | quantize(color.y, 63) << 5 | quantize(color.z, 31)) as u16
+    };
+    let from_565 = |color: u16| {
+        Vec4::new(
+            (color >> 11) as f32 / 31.0,
+            ((color >> 5) & 63) as f32 / 63.0,
+            (color & 31) as f32 / 31.0,
+            0.0,
+        )
+    };
+
+    let pixels = pixels.map(|pixel| pixel.truncate().extend(0.0));
+    let min = pixels.iter().copied().reduce(Vec4::min).unwrap();
+    let max = pixels.iter().copied().reduce(Vec4::max).unwrap();
+
+    let mut color0 = to_565(max);
+    let mut color1 = to_565(min);
+
+    // the four color mode requires the first color to be larger than the second one
+    if color0 < color1 {
+        (color0, color1) = (color1, color0);
+    }
+
+    let mut indices = 0;
+
+    if color0 != color1 {
+        let (end0, end1) = (from_565(color0), from_565(color1));
+        let palette = [
+            end0,
+            end1,
+            end0.lerp(end1, 1.0 / 3.0),
+            end0.lerp(end1, 2.0 / 3.0),
+        ];
+
+        for (i, &pixel) in pixels.iter().enumerate() {
+            indices |= closest(&palette, pixel) << (2 * i);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..8].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Encodes a single channel of the block with the eight value mode of BC4.
+fn encode_bc4(pixels: &[Vec4; 16], channel: usize) -> [u8; 8] {
+    let values = pixels.map(|pixel| quantize(pixel[channel], 255) as f32);
+    let red0 = values.iter().copied().fold(0.0, f32::max);
+    let red1 = values.iter().copied().fold(255.0, f32::min);
+
+    let mut indices = 0u64;
+
+    if red0 != red1 {
+        let mut palette = [Vec4::ZERO; 8];
+        palette[0].x = red0;
+        palette[1].x = red1;
+
+        for i in 1..7 {
+            palette[i + 1].x = ((7 - i) as f32 * red0 + i as f32 * red1) / 7.0;
+        }
+
+        for (i, &value) in values.iter().enumerate() {
+            indices |= (closest(&palette, Vec4::new(value, 0.0, 0.0, 0.0)) as u64) << (3 * i);
+        }
+    }
+
+    let mut block = [0; 8];
+    block[0] = red0 as u8;
+    block[1] = red1 as u8;
+    block[2..8].copy_from_slice(&indices.to_le_bytes()[0..6]);
+    block
+}
+
+/// Encodes the block with mode 6 of BC7, which uses a single pair of RGBA endpoints
+/// and 4 bit indices.
+fn encode_bc7(pixels: &[Vec4; 16]) -> [u8; 16] {
+    let min = pixels.iter().copied().reduce(Vec4::min).unwrap();
+    let max = pixels.iter().copied().reduce(Vec4::max).unwrap();
+
+    // each endpoint consists of 7 bits per channel and a shared least significant p-bit
+    let quantize_endpoint = |color: Vec4| {
+        [0, 1]
+            .into_iter()
+            .map(|p_bit| {
+                let channels = color
+                    .to_array()
+                    .map(|value| ((quantize(value, 255) as i32 - p_bit) / 2).clamp(0, 127) as u32);
+                let endpoint = channels.map(|channel| (channel << 1) | p_bit as u32);
+                let error = (Vec4::from_array(endpoint.map(|value| value as f32)) / 255.0)
+                    .distance_squared(color);
+
+                (channels, p_bit as u32, endpoint, error)
+            })
+            .min_by(|a, b| a.3.total_cmp(&b.3))
+            .unwrap()
+    };
+
+    let mut end0 = quantize_endpoint(min);
+    let mut end1 = quantize_endpoint(max);
+
+    let interpolate = |end0: [u32; 4], end1: [u32; 4]| {
+        BC7_WEIGHTS.map(|weight| {
+            Vec4::from_array(std::array::from_fn(|c| {
+                (((64 - weight) * end0[c] + weight * end1[c] + 32) >> 6) as f32 / 255.0
+            }))
+        })
+    };
+
+    let palette = interpolate(end0.2, end1.2);
+    let mut indices = pixels.map(|pixel| closest(&palette, pixel));
+
+    // the most significant bit of the first index is implicitly zero
+    if indices[0] >= 8 {
+        (end0, end1) = (end1, end0);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = 1u128 << 6;
+    let mut offset = 7;
+
+    let mut write = |value: u32, count: u32| {
+        bits |= (value as u128) << offset;
+        offset += count;
+    };
+
+    for channel in 0..4 {
+        write(end0.0[channel], 7);
+        write(end1.0[channel], 7);
+    }
+
+    write(end0.1, 1);
+    write(end1.1, 1);
+
+    for (i, index) in indices.into_iter().enumerate() {
+        write(index, if i == 0 { 3 } else { 4 });
+    }
+
+    bits.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interpolates the pixels of the block between two colors.
+    fn gradient(start: Vec4, end: Vec4) -> [Vec4; 16] {
+        std::array::from_fn(|i| start.lerp(end, i as f32 / 15.0))
+    }
+
+    fn decode_bc1(block: [u8; 8]) -> [Vec4; 16] {
+        let from_565 = |color: u16| {
+            Vec4::new(
+                (color >> 11) as f32 / 31.0,
+                ((color >> 5) & 63) as f32 / 63.0,
+                (color & 31) as f32 / 31.0,
+                0.0,
+            )
+        };
+
+        let end0 = from_565(u16::from_le_bytes([block[0], block[1]]));
+        let end1 = from_565(u16::from_le_bytes([block[2], block[3]]));
+        let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+
+        let palette = [
+            end0,
+            end1,
+            end0.lerp(end1, 1.0 / 3.0),
+            end0.lerp(end1, 2.0 / 3.0),
+        ];
+
+        std::array::from_fn(|i| palette[(indices >> (2 * i) & 3) as usize])
+    }
+
+    fn decode_bc4(block: [u8; 8]) -> [f32; 16] {
+        let (red0, red1) = (block[0] as f32, block[1] as f32);
+        let mut indices = [0; 8];
+        indices[0..6].copy_from_slice(&block[2..8]);
+        let indices = u64::from_le_bytes(indices);
+
+        std::array::from_fn(|i| {
+            let value = match indices >> (3 * i) & 7 {
+                0 => red0,
+                1 => red1,
+                index => ((8 - index) as f32 * red0 + (index - 1) as f32 * red1) / 7.0,
+            };
+
+            value / 255.0
+        })
+    }
+
+    fn decode_bc7(block: [u8; 16]) -> [Vec4; 16] {
+        let bits = u128::from_le_bytes(block);
+        let mut offset = 0;
+
+        let mut read = |count: u32| {
+            let value = (bits >> offset) as u32 & ((1 << count) - 1);
+            offset += count;
+            value
+        };
+
+        assert_eq!(read(7), 1 << 6, "The block is not encoded with mode 6.");
+
+        let mut end0 = [0; 4];
+        let mut end1 = [0; 4];
+
+        for channel in 0..4 {
+            end0[channel] = read(7);
+            end1[channel] = read(7);
+        }
+
+        let (p_bit0, p_bit1) = (read(1), read(1));
+        let end0 = end0.map(|channel| channel << 1 | p_bit0);
+        let end1 = end1.map(|channel| channel << 1 | p_bit1);
+
+        std::array::from_fn(|i| {
+            let weight = BC7_WEIGHTS[read(if i == 0 { 3 } else { 4 }) as usize];
+
+            Vec4::from_array(std::array::from_fn(|c| {
+                (((64 - weight) * end0[c] + weight * end1[c] + 32) >> 6) as f32 / 255.0
+            }))
+        })
+    }
+
+    #[test]
+    fn bc1_round_trip() {
+        let pixels = gradient(Vec4::new(0.1, 0.2, 0.3, 1.0), Vec4::new(0.6, 0.5, 0.9, 1.0));
+        let decoded = decode_bc1(encode_bc1(&pixels));
+
+        for (pixel, decoded) in pixels.iter().zip(decoded) {
+            // half the distance between two palette entries and the quantization to 5 bits
+            let error = (pixel.truncate() - decoded.truncate()).abs().max_element();
+            assert!(
+                error < 0.6 / 6.0 + 1.0 / 62.0,
+                "{pixel} was decoded as {decoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn bc4_round_trip() {
+        let pixels = gradient(Vec4::splat(0.1), Vec4::splat(0.9));
+        let decoded = decode_bc4(encode_bc4(&pixels, 0));
+
+        for (pixel, decoded) in pixels.iter().zip(decoded) {
+            // half the distance between two palette entries and the quantization to 8 bits
+            let error = (pixel.x - decoded).abs();
+            assert!(
+                error < 0.8 / 14.0 + 1.0 / 255.0,
+                "{pixel} was decoded as {decoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn bc7_round_trip() {
+        let pixels = gradient(Vec4::new(0.1, 0.2, 0.3, 0.5), Vec4::new(0.6, 0.5, 0.9, 1.0));
+        let decoded = decode_bc7(encode_bc7(&pixels));
+
+        for (pixel, decoded) in pixels.iter().zip(decoded) {
+            // half the distance between two palette entries and the quantization to 7 bits
+            let error = (*pixel - decoded).abs().max_element();
+            assert!(
+                error < 0.6 / 30.0 + 1.0 / 127.0,
+                "{pixel} was decoded as {decoded}"
+            );
+        }
+    }
+}
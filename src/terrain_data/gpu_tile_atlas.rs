@@ -1,6 +1,7 @@
 use crate::{
     terrain::TerrainComponents,
     terrain_data::{
+        block_compression::BlockCompression,
         tile_atlas::{
             AtlasAttachment, AtlasTileAttachment, AtlasTileAttachmentWithData, TileAtlas,
         },
@@ -61,6 +62,7 @@ pub(crate) struct AtlasBufferInfo {
     pub(crate) border_size: u32,
     pub(crate) center_size: u32,
     format: AttachmentFormat,
+    block_compression: BlockCompression,
     mip_level_count: u32,
 
     pixels_per_entry: u32,
@@ -77,7 +79,11 @@ pub(crate) struct AtlasBufferInfo {
 }
 
 impl AtlasBufferInfo {
-    fn new(attachment: &AtlasAttachment, lod_count: u32) -> Self {
+    fn new(
+        attachment: &AtlasAttachment,
+        lod_count: u32,
+        block_compression: BlockCompression,
+    ) -> Self {
         // Todo: adjust this code for pixel sizes larger than 4 byte
         // This approach is currently limited to 1, 2, and 4 byte sized pixels
         // Extending it to 8 and 16 sized pixels should be quite easy.
@@ -90,7 +96,7 @@ impl AtlasBufferInfo {
         let center_size = attachment.center_size;
         let mip_level_count = attachment.mip_level_count;
 
-        let pixel_size = format.pixel_size();
+        let pixel_size = format.texture_pixel_size();
         let entry_size = mem::size_of::<u32>() as u32;
         let pixels_per_entry = entry_size / pixel_size;
 
@@ -118,6 +124,7 @@ impl AtlasBufferInfo {
             actual_tile_size,
             aligned_tile_size,
             format,
+            block_compression,
             workgroup_count,
         }
     }
@@ -150,7 +157,18 @@ impl AtlasBufferInfo {
         }
     }
 
+    /// The size of the mip level, which is padded to full blocks for block compressed attachments.
     fn image_copy_size(&self, mip_level: u32) -> Extent3d {
+        if self.block_compression != BlockCompression::None {
+            let size = 4 * BlockCompression::blocks_per_side(self.texture_size, mip_level);
+
+            return Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            };
+        }
+
         Extent3d {
             width: self.texture_size >> mip_level,
             height: self.texture_size >> mip_level,
@@ -198,10 +216,12 @@ impl GpuAtlasAttachment {
         tile_atlas: &TileAtlas,
     ) -> Self {
         let name = attachment.name.clone();
-        let max_atlas_write_slots = tile_atlas.state.max_atlas_write_slots;
+        let block_compression = tile_atlas.gpu_block_compression(attachment);
+
+        let max_atlas_write_slots = tile_atlas.state.settings.atlas_write_slots;
         let atlas_write_slots = Vec::with_capacity(max_atlas_write_slots as usize);
 
-        let buffer_info = AtlasBufferInfo::new(attachment, tile_atlas.lod_count);
+        let buffer_info = AtlasBufferInfo::new(attachment, tile_atlas.lod_count, block_compression);
 
         // dbg!(&buffer_info);
 
@@ -215,15 +235,23 @@ impl GpuAtlasAttachment {
             mip_level_count: attachment.mip_level_count,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: buffer_info.format.render_format(),
+            format: buffer_info
+                .block_compression
+                .render_format(buffer_info.format),
             usage: TextureUsages::COPY_DST
                 | TextureUsages::COPY_SRC
                 | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[buffer_info.format.processing_format()],
+            view_formats: &[buffer_info
+                .block_compression
+                .processing_format(buffer_info.format)],
         });
 
         let atlas_view = atlas_texture.create_view(&TextureViewDescriptor {
-            format: Some(buffer_info.format.processing_format()),
+            format: Some(
+                buffer_info
+                    .block_compression
+                    .processing_format(buffer_info.format),
+            ),
             ..default()
         });
 
@@ -308,6 +336,43 @@ impl GpuAtlasAttachment {
 
     fn upload_tiles(&mut self, queue: &RenderQueue) {
         for tile in self.upload_tiles.drain(..) {
+            // the stored blocks are ignored, while the terrain is preprocessed
+            if let Some(blocks) = tile
+                .blocks
+                .as_ref()
+                .filter(|_| self.buffer_info.block_compression != BlockCompression::None)
+            {
+                let block_size = self.buffer_info.block_compression.block_size();
+                let mut start = 0;
+
+                for mip_level in 0..self.buffer_info.mip_level_count {
+                    let blocks_per_side =
+                        BlockCompression::blocks_per_side(self.buffer_info.texture_size, mip_level);
+                    let end = start + (blocks_per_side * blocks_per_side * block_size) as usize;
+
+                    queue.write_texture(
+                        self.buffer_info.image_copy_texture(
+                            &self.atlas_texture,
+                            tile.tile.atlas_index,
+                            mip_level,
+                        ),
+                        &blocks[start..end],
+                        ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(blocks_per_side * block_size),
+                            rows_per_image: Some(blocks_per_side),
+                        },
+                        self.buffer_info.image_copy_size(mip_level),
+                    );
+
+                    start = end;
+                }
+
+                continue;
+            }
+
+            // expanded once, since it may convert the whole tile (e.g. Rgb8 to Rgba8)
+            let data = tile.data.texture_bytes();
             let mut start = 0;
 
             for mip_level in 0..self.buffer_info.mip_level_count {
@@ -321,7 +386,7 @@ impl GpuAtlasAttachment {
                         tile.tile.atlas_index,
                         mip_level,
                     ),
-                    &tile.data.bytes()[start..end],
+                    &data[start..end],
                     ImageDataLayout {
                         offset: 0,
                         bytes_per_row: Some(side_size),
@@ -403,7 +468,8 @@ impl GpuAtlasAttachment {
 
                     AtlasTileAttachmentWithData {
                         tile,
-                        data: AttachmentData::from_bytes(&data, buffer_info.format),
+                        data: AttachmentData::from_texture_bytes(&data, buffer_info.format),
+                        blocks: None,
                         texture_size: buffer_info.texture_size,
                     }
                 })
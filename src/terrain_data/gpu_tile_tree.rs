@@ -59,8 +59,8 @@ impl GpuTileTree {
         tile_trees: Extract<Res<TerrainViewComponents<TileTree>>>,
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
-            if gpu_tile_trees.contains_key(&(terrain, view)) {
-                return;
+            if !tile_tree.rendered || gpu_tile_trees.contains_key(&(terrain, view)) {
+                continue;
             }
 
             gpu_tile_trees.insert((terrain, view), GpuTileTree::new(&device, tile_tree));
@@ -73,7 +73,9 @@ impl GpuTileTree {
         tile_trees: Extract<Res<TerrainViewComponents<TileTree>>>,
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter() {
-            let gpu_tile_tree = gpu_tile_trees.get_mut(&(terrain, view)).unwrap();
+            let Some(gpu_tile_tree) = gpu_tile_trees.get_mut(&(terrain, view)) else {
+                continue;
+            };
 
             gpu_tile_tree.data = tile_tree.data.clone();
             gpu_tile_tree.origins = tile_tree.origins.clone();
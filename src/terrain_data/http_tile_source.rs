@@ -0,0 +1,273 @@
+use crate::{
+    formats::TerrainManifest, math::TileCoordinate, terrain_data::tile_source::TileSource,
+};
+use anyhow::{anyhow, bail, Result};
+use async_channel::Sender;
+use bevy::utils::BoxedFuture;
+use lru::LruCache;
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime},
+};
+use ureq::Agent;
+
+/// The amount of threads, which execute the HTTP requests.
+const WORKER_COUNT: usize = 8;
+
+/// The URL of a tile and the channel its data is sent back through.
+type Request = (String, Sender<Result<Vec<u8>>>);
+
+/// Hashes the bytes with the 64 bit FNV-1a hash, which is stable across runs and platforms.
+fn stable_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// A size limited cache, which stores tiles on disk.
+///
+/// The least recently used tiles are evicted, once the size limit is exceeded.
+/// Tiles cached by previous runs are reused.
+struct TileCache {
+    directory: PathBuf,
+    max_size: u64,
+    size: u64,
+    entries: LruCache<PathBuf, u64>,
+}
+
+impl TileCache {
+    fn open(directory: PathBuf, max_size: u64) -> Self {
+        fn collect_files(directory: &Path, files: &mut Vec<(SystemTime, PathBuf, u64)>) {
+            let Ok(entries) = fs::read_dir(directory) else {
+                return;
+            };
+
+            for entry in entries.flatten() {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    collect_files(&entry.path(), files);
+                } else {
+                    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    files.push((modified, entry.path(), metadata.len()));
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+        collect_files(&directory, &mut files);
+        files.sort();
+
+        let mut cache = Self {
+            directory,
+            max_size,
+            size: 0,
+            entries: LruCache::unbounded(),
+        };
+
+        for (_, path, size) in files {
+            cache.size += size;
+            cache.entries.push(path, size);
+        }
+
+        cache.evict();
+        cache
+    }
+
+    fn tile_path(&self, attachment: &str, coordinate: TileCoordinate, extension: &str) -> PathBuf {
+        self.directory
+            .join(attachment)
+            .join(format!("{coordinate}.{extension}"))
+    }
+
+    fn read(&mut self, path: &Path) -> Option<Vec<u8>> {
+        self.entries.get(path)?;
+
+        match fs::read(path) {
+            Ok(data) => Some(data),
+            Err(_) => {
+                // the file was removed externally
+                if let Some(size) = self.entries.pop(path) {
+                    self.size -= size;
+                }
+                None
+            }
+        }
+    }
+
+    fn write(&mut self, path: PathBuf, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, data)?;
+
+        if let Some(size) = self.entries.put(path, data.len() as u64) {
+            self.size -= size;
+        }
+
+        self.size += data.len() as u64;
+        self.evict();
+
+        Ok(())
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            let Some((path, size)) = self.entries.pop_lru() else {
+                break;
+            };
+
+            let _ = fs::remove_file(path);
+            self.size -= size;
+        }
+    }
+}
+
+/// A read-only [`TileSource`], which streams the tiles of a preprocessed terrain from an HTTP server.
+///
+/// The URL of each tile is created from a template, where the placeholders `{attachment}`,
+/// `{coordinate}`, `{side}`, `{lod}`, `{x}`, `{y}` and `{extension}` are replaced accordingly.
+/// To serve the directory layout of the [`FileTileSource`](super::tile_source::FileTileSource),
+/// use a template like `http://localhost:8000/terrains/planar/data/{attachment}/{coordinate}.{extension}`.
+///
+/// The requests are executed by a fixed amount of worker threads, so that they do not block
+/// the task pool, while the amount of pending requests is limited by the load slots of the tile atlas.
+pub struct HttpTileSource {
+    url_template: String,
+    agent: Agent,
+    requests: Sender<Request>,
+    cache: Option<Arc<Mutex<TileCache>>>,
+}
+
+impl HttpTileSource {
+    /// Creates a new HTTP tile source from the URL template.
+    pub fn new(url_template: &str) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .build();
+        let (requests, receiver) = async_channel::unbounded::<Request>();
+
+        for _ in 0..WORKER_COUNT {
+            let agent = agent.clone();
+            let receiver = receiver.clone();
+
+            // the workers exit, once the tile source is dropped
+            thread::spawn(move || {
+                while let Ok((url, response)) = receiver.recv_blocking() {
+                    let _ = response.try_send(Self::fetch(&agent, &url));
+                }
+            });
+        }
+
+        Self {
+            url_template: url_template.to_string(),
+            agent,
+            requests,
+            cache: None,
+        }
+    }
+
+    /// Caches the fetched tiles inside the directory, until they exceed the maximum size in bytes.
+    ///
+    /// The tiles are cached inside a subdirectory named after the hash of the URL template,
+    /// so that the tiles of different servers or terrains can share the same cache directory.
+    pub fn with_cache(mut self, directory: &str, max_size: u64) -> Self {
+        let directory = Path::new(directory).join(format!(
+            "{:016x}",
+            stable_hash(self.url_template.as_bytes())
+        ));
+
+        self.cache = Some(Arc::new(Mutex::new(TileCache::open(directory, max_size))));
+        self
+    }
+
+    /// Downloads the [`TerrainManifest`] from the URL and stores it inside the terrain folder.
+    ///
+    /// The tile atlas only requests the tiles listed in the manifest,
+    /// so this has to be called before the terrain is spawned.
+    pub fn fetch_manifest(&self, url: &str, path: &str) -> Result<()> {
+        let data = Self::fetch(&self.agent, url)?;
+        let path = TerrainManifest::path(path);
+
+        fs::create_dir_all(Path::new(&path).parent().unwrap())?;
+        fs::write(path, data)?;
+
+        Ok(())
+    }
+
+    fn tile_url(&self, attachment: &str, coordinate: TileCoordinate, extension: &str) -> String {
+        self.url_template
+            .replace("{attachment}", attachment)
+            .replace("{coordinate}", &coordinate.to_string())
+            .replace("{side}", &coordinate.side.to_string())
+            .replace("{lod}", &coordinate.lod.to_string())
+            .replace("{x}", &coordinate.x.to_string())
+            .replace("{y}", &coordinate.y.to_string())
+            .replace("{extension}", extension)
+    }
+
+    fn fetch(agent: &Agent, url: &str) -> Result<Vec<u8>> {
+        let response = agent.get(url).call()?;
+
+        let mut data = Vec::new();
+        response.into_reader().read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+impl TileSource for HttpTileSource {
+    fn load_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let cache_path = self.cache.as_ref().map(|cache| {
+                cache
+                    .lock()
+                    .unwrap()
+                    .tile_path(attachment, coordinate, extension)
+            });
+
+            if let (Some(cache), Some(path)) = (&self.cache, &cache_path) {
+                if let Some(data) = cache.lock().unwrap().read(path) {
+                    return Ok(data);
+                }
+            }
+
+            let url = self.tile_url(attachment, coordinate, extension);
+            let (tx, rx) = async_channel::bounded(1);
+
+            self.requests
+                .send((url, tx))
+                .await
+                .map_err(|_| anyhow!("The HTTP workers have stopped."))?;
+
+            let data = rx.recv().await??;
+
+            if let (Some(cache), Some(path)) = (&self.cache, cache_path) {
+                cache.lock().unwrap().write(path, &data)?;
+            }
+
+            Ok(data)
+        })
+    }
+
+    fn save_tile<'a>(
+        &'a self,
+        _attachment: &'a str,
+        coordinate: TileCoordinate,
+        _extension: &'a str,
+        _data: &'a [u8],
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            bail!("Can not save the tile {coordinate}, since the HTTP tile source is read-only.")
+        })
+    }
+}
@@ -9,32 +9,52 @@
 //! To decide which tiles should be currently loaded you can create multiple
 //! [`TileTree`] views that correspond to one tile atlas.
 //! These tile_trees request and release tiles from the tile atlas based on their quality
-//! setting (`load_distance`).
+//! setting (`load_distance`) and their [`RequestPolicy`](request_policy::RequestPolicy).
 //! Additionally they are then used to access the best loaded data at any position.
 //!
 //! Both the tile atlas and the tile_trees also have a corresponding GPU representation,
 //! which can be used to access the terrain data in shaders.
 
 use crate::{
-    terrain_data::{tile_atlas::TileAtlas, tile_tree::TileTree},
+    terrain_data::{
+        block_compression::BlockCompression,
+        tile_atlas::{R16Image, Rg16Image, Rgb8Image, Rgba8Image, TileAtlas},
+        tile_tree::TileTree,
+    },
     util::CollectArray,
 };
+use anyhow::{anyhow, bail, ensure, Result};
 use bevy::{math::DVec3, prelude::*, render::render_resource::*};
 use bincode::{Decode, Encode};
-use bytemuck::cast_slice;
-use itertools::iproduct;
-use std::iter;
-
+use bytemuck::{cast_slice, try_cast_slice, Pod};
+use half::f16;
+use image::{ColorType, DynamicImage, ImageFormat, ImageReader};
+use itertools::{iproduct, Itertools};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt::Debug, io::Cursor, iter};
+
+pub mod block_compression;
 pub mod gpu_tile_atlas;
 pub mod gpu_tile_tree;
+#[cfg(feature = "http")]
+pub mod http_tile_source;
+pub mod request_policy;
+pub mod statistics;
 pub mod tile_atlas;
+pub mod tile_source;
 pub mod tile_tree;
 
 pub const INVALID_ATLAS_INDEX: u32 = u32::MAX;
 pub const INVALID_LOD: u32 = u32::MAX;
 
 /// The data format of an attachment.
-#[derive(Encode, Decode, Clone, Copy, Debug)]
+///
+/// Single channel formats store the heights of the terrain.
+/// Unorm values map [0, 1] and Snorm values map [-1, 1] to the height range of the
+/// [`TerrainModel`](crate::math::TerrainModel), while float values are heights in meters.
+/// Note that [`AttachmentFormat::R16F`] only has an 11 bit mantissa, so its precision
+/// decreases with the distance from sea level (e.g. 2 m steps above 2048 m).
+#[derive(Encode, Decode, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AttachmentFormat {
     /// Three channels  8 bit
     Rgb8,
@@ -44,6 +64,14 @@ pub enum AttachmentFormat {
     R16,
     /// Two   channels 16 bit
     Rg16,
+    /// One   channel  32 bit float
+    ///
+    /// Requires the [`WgpuFeatures::FLOAT32_FILTERABLE`] feature of the render device.
+    R32F,
+    /// One   channel  16 bit float
+    R16F,
+    /// One   channel  16 bit signed
+    R16Snorm,
 }
 
 impl AttachmentFormat {
@@ -53,6 +81,21 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => 0,
             AttachmentFormat::R16 => 1,
             AttachmentFormat::Rg16 => 3,
+            AttachmentFormat::R32F => 6,
+            AttachmentFormat::R16F => 7,
+            AttachmentFormat::R16Snorm => 8,
+        }
+    }
+
+    /// The offset and scale, which convert a sample of this format into a height in meters.
+    pub(crate) fn height_transform(self, min_height: f32, max_height: f32) -> (f32, f32) {
+        match self {
+            AttachmentFormat::R32F | AttachmentFormat::R16F => (0.0, 1.0),
+            AttachmentFormat::R16Snorm => (
+                (min_height + max_height) / 2.0,
+                (max_height - min_height) / 2.0,
+            ),
+            _ => (min_height, max_height - min_height),
         }
     }
     pub(crate) fn render_format(self) -> TextureFormat {
@@ -61,6 +104,9 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => TextureFormat::Rgba8UnormSrgb,
             AttachmentFormat::R16 => TextureFormat::R16Unorm,
             AttachmentFormat::Rg16 => TextureFormat::Rg16Unorm,
+            AttachmentFormat::R32F => TextureFormat::R32Float,
+            AttachmentFormat::R16F => TextureFormat::R16Float,
+            AttachmentFormat::R16Snorm => TextureFormat::R16Snorm,
         }
     }
 
@@ -70,6 +116,17 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => TextureFormat::Rgba8Unorm,
             AttachmentFormat::R16 => TextureFormat::R16Unorm,
             AttachmentFormat::Rg16 => TextureFormat::Rg16Unorm,
+            AttachmentFormat::R32F => TextureFormat::R32Float,
+            AttachmentFormat::R16F => TextureFormat::R16Float,
+            AttachmentFormat::R16Snorm => TextureFormat::R16Snorm,
+        }
+    }
+
+    /// The features the render device requires to sample the attachment.
+    pub(crate) fn required_features(self) -> WgpuFeatures {
+        match self {
+            AttachmentFormat::R32F => WgpuFeatures::FLOAT32_FILTERABLE,
+            _ => WgpuFeatures::empty(),
         }
     }
 
@@ -79,8 +136,136 @@ impl AttachmentFormat {
             AttachmentFormat::Rgba8 => 4,
             AttachmentFormat::R16 => 2,
             AttachmentFormat::Rg16 => 4,
+            AttachmentFormat::R32F => 4,
+            AttachmentFormat::R16F => 2,
+            AttachmentFormat::R16Snorm => 2,
+        }
+    }
+
+    /// The size of a pixel inside the atlas texture.
+    /// There is no three channel texture format, thus [`AttachmentFormat::Rgb8`] is expanded to four channels.
+    pub(crate) fn texture_pixel_size(self) -> u32 {
+        match self {
+            AttachmentFormat::Rgb8 => 4,
+            format => format.pixel_size(),
+        }
+    }
+
+    pub(crate) fn sample_size(self) -> u32 {
+        match self {
+            AttachmentFormat::Rgb8 => 1,
+            AttachmentFormat::Rgba8 => 1,
+            AttachmentFormat::R16 => 2,
+            AttachmentFormat::Rg16 => 2,
+            AttachmentFormat::R32F => 4,
+            AttachmentFormat::R16F => 2,
+            AttachmentFormat::R16Snorm => 2,
+        }
+    }
+}
+
+/// The storage format of the tiles of an attachment.
+#[derive(Encode, Decode, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttachmentStorage {
+    /// The tiles are stored as raw binary data.
+    #[default]
+    Raw,
+    /// The tiles are stored as PNG images, which is useful for inspecting them.
+    /// Supports the [`AttachmentFormat::Rgb8`], [`AttachmentFormat::Rgba8`],
+    /// [`AttachmentFormat::R16`] and [`AttachmentFormat::Rg16`] formats.
+    Png,
+    /// The tiles are compressed with LZ4.
+    Lz4,
+    /// Each sample is replaced by its difference to the same channel of the previous pixel,
+    /// before the tile is compressed with LZ4.
+    /// This is lossless and works well for smooth data like heights.
+    DeltaLz4,
+}
+
+impl AttachmentStorage {
+    pub(crate) fn is_supported(self, format: AttachmentFormat) -> bool {
+        match self {
+            AttachmentStorage::Png => matches!(
+                format,
+                AttachmentFormat::Rgb8
+                    | AttachmentFormat::Rgba8
+                    | AttachmentFormat::R16
+                    | AttachmentFormat::Rg16
+            ),
+            _ => true,
+        }
+    }
+
+    /// The file extension of the stored tiles.
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            AttachmentStorage::Png => "png",
+            _ => "bin",
+        }
+    }
+}
+
+fn delta_encode(data: &[u8], format: AttachmentFormat) -> Vec<u8> {
+    let pixel_size = format.pixel_size() as usize;
+    let mut encoded = data.to_vec();
+
+    match format.sample_size() {
+        1 => {
+            for i in pixel_size..data.len() {
+                encoded[i] = data[i].wrapping_sub(data[i - pixel_size]);
+            }
+        }
+        2 => {
+            let sample = |i: usize| u16::from_ne_bytes([data[i], data[i + 1]]);
+
+            for i in (pixel_size..data.len()).step_by(2) {
+                let delta = sample(i).wrapping_sub(sample(i - pixel_size));
+                encoded[i..i + 2].copy_from_slice(&delta.to_ne_bytes());
+            }
+        }
+        4 => {
+            let sample = |i: usize| u32::from_ne_bytes(data[i..i + 4].try_into().unwrap());
+
+            for i in (pixel_size..data.len()).step_by(4) {
+                let delta = sample(i).wrapping_sub(sample(i - pixel_size));
+                encoded[i..i + 4].copy_from_slice(&delta.to_ne_bytes());
+            }
         }
+        _ => unreachable!(),
     }
+
+    encoded
+}
+
+fn delta_decode(mut data: Vec<u8>, format: AttachmentFormat) -> Vec<u8> {
+    let pixel_size = format.pixel_size() as usize;
+
+    match format.sample_size() {
+        1 => {
+            for i in pixel_size..data.len() {
+                data[i] = data[i].wrapping_add(data[i - pixel_size]);
+            }
+        }
+        2 => {
+            for i in (pixel_size..data.len()).step_by(2) {
+                let delta = u16::from_ne_bytes([data[i], data[i + 1]]);
+                let previous = u16::from_ne_bytes([data[i - pixel_size], data[i - pixel_size + 1]]);
+                data[i..i + 2].copy_from_slice(&delta.wrapping_add(previous).to_ne_bytes());
+            }
+        }
+        4 => {
+            for i in (pixel_size..data.len()).step_by(4) {
+                let delta = u32::from_ne_bytes(data[i..i + 4].try_into().unwrap());
+                let previous = u32::from_ne_bytes(
+                    data[i - pixel_size..i - pixel_size + 4].try_into().unwrap(),
+                );
+                data[i..i + 4].copy_from_slice(&delta.wrapping_add(previous).to_ne_bytes());
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    data
 }
 
 /// Configures an attachment.
@@ -94,6 +279,11 @@ pub struct AttachmentConfig {
     pub mip_level_count: u32,
     /// The format of the attachment.
     pub format: AttachmentFormat,
+    /// The storage format of the tiles of the attachment.
+    pub storage: AttachmentStorage,
+    /// The block compression of the attachment inside the GPU atlas.
+    /// The blocks are encoded by the preprocessor, so this has to match the one the terrain was preprocessed with.
+    pub block_compression: BlockCompression,
 }
 
 impl Default for AttachmentConfig {
@@ -104,51 +294,231 @@ impl Default for AttachmentConfig {
             border_size: 1,
             mip_level_count: 1,
             format: AttachmentFormat::R16,
+            storage: AttachmentStorage::Raw,
+            block_compression: BlockCompression::None,
         }
     }
 }
 
+impl AttachmentConfig {
+    /// The amount of bytes a tile of this attachment occupies in main memory, including all mip levels.
+    pub fn cpu_tile_size(&self) -> u64 {
+        (0..self.mip_level_count)
+            .map(|mip_level| {
+                let size = (self.texture_size >> mip_level).max(1) as u64;
+                size * size * self.format.pixel_size() as u64
+            })
+            .sum()
+    }
+
+    /// The amount of bytes a tile of this attachment occupies in video memory, including all mip levels.
+    pub fn gpu_tile_size(&self) -> u64 {
+        (0..self.mip_level_count)
+            .map(|mip_level| {
+                if self.block_compression == BlockCompression::None {
+                    let size = (self.texture_size >> mip_level).max(1) as u64;
+                    size * size * self.format.texture_pixel_size() as u64
+                } else {
+                    let blocks =
+                        BlockCompression::blocks_per_side(self.texture_size, mip_level) as u64;
+                    blocks * blocks * self.block_compression.block_size() as u64
+                }
+            })
+            .sum()
+    }
+}
+
 #[derive(Clone)]
 pub(crate) enum AttachmentData {
     None,
     /// Three channels  8 bit
-    // Rgb8(Vec<(u8, u8, u8)>), Can not be represented currently
+    Rgb8(Vec<[u8; 3]>),
     /// Four  channels  8 bit
     Rgba8(Vec<[u8; 4]>),
     /// One   channel  16 bit
     R16(Vec<u16>),
     /// Two   channels 16 bit
     Rg16(Vec<[u16; 2]>),
+    /// One   channel  32 bit float
+    R32F(Vec<f32>),
+    /// One   channel  16 bit float
+    R16F(Vec<f16>),
+    /// One   channel  16 bit signed
+    R16Snorm(Vec<i16>),
 }
 
 impl AttachmentData {
-    pub(crate) fn from_bytes(data: &[u8], format: AttachmentFormat) -> Self {
+    fn cast(data: &[u8], format: AttachmentFormat) -> Result<Self> {
+        fn cast<T: Pod>(data: &[u8]) -> Result<Vec<T>> {
+            try_cast_slice(data)
+                .map(<[T]>::to_vec)
+                .map_err(|error| anyhow!("The tile data can not be cast: {error}."))
+        }
+
+        Ok(match format {
+            AttachmentFormat::Rgb8 => Self::Rgb8(cast(data)?),
+            AttachmentFormat::Rgba8 => Self::Rgba8(cast(data)?),
+            AttachmentFormat::R16 => Self::R16(cast(data)?),
+            AttachmentFormat::Rg16 => Self::Rg16(cast(data)?),
+            AttachmentFormat::R32F => Self::R32F(cast(data)?),
+            AttachmentFormat::R16F => Self::R16F(cast(data)?),
+            AttachmentFormat::R16Snorm => Self::R16Snorm(cast(data)?),
+        })
+    }
+
+    /// Creates the attachment data from the bytes of a tile without mip levels.
+    pub(crate) fn from_bytes(
+        data: &[u8],
+        format: AttachmentFormat,
+        texture_size: u32,
+    ) -> Result<Self> {
+        let size = texture_size as usize * texture_size as usize * format.pixel_size() as usize;
+
+        ensure!(
+            data.len() == size,
+            "The tile has {} bytes, but {size} bytes are required for the {format:?} format and the texture size {texture_size}.",
+            data.len()
+        );
+
+        Self::cast(data, format)
+    }
+
+    /// Creates the attachment data from the bytes of the atlas texture.
+    pub(crate) fn from_texture_bytes(data: &[u8], format: AttachmentFormat) -> Self {
         match format {
-            AttachmentFormat::Rgb8 => unimplemented!(),
-            AttachmentFormat::Rgba8 => Self::Rgba8(cast_slice(data).to_vec()),
-            AttachmentFormat::R16 => Self::R16(cast_slice(data).to_vec()),
-            AttachmentFormat::Rg16 => Self::Rg16(cast_slice(data).to_vec()),
+            AttachmentFormat::Rgb8 => Self::Rgb8(
+                data.chunks_exact(4)
+                    .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                    .collect(),
+            ),
+            format => Self::cast(data, format).unwrap(),
         }
     }
 
+    /// Decodes the stored data of a tile.
+    ///
+    /// Fails, if the data does not match the format and texture size of the attachment.
+    pub(crate) fn decode(
+        data: Vec<u8>,
+        storage: AttachmentStorage,
+        format: AttachmentFormat,
+        texture_size: u32,
+    ) -> Result<Self> {
+        let data = match storage {
+            AttachmentStorage::Raw => data,
+            AttachmentStorage::Png => {
+                let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+                reader.no_limits();
+                let image = reader.decode()?;
+
+                let color_type = match format {
+                    AttachmentFormat::Rgb8 => ColorType::Rgb8,
+                    AttachmentFormat::Rgba8 => ColorType::Rgba8,
+                    AttachmentFormat::R16 => ColorType::L16,
+                    AttachmentFormat::Rg16 => ColorType::La16,
+                    _ => bail!("The {format:?} format can not be stored as PNG."),
+                };
+
+                // the color type also determines the bit depth
+                ensure!(
+                    image.color() == color_type,
+                    "The PNG has the color type {:?}, but the {format:?} format requires {color_type:?}.",
+                    image.color()
+                );
+
+                image.into_bytes()
+            }
+            AttachmentStorage::Lz4 => lz4_flex::decompress_size_prepended(&data)?,
+            AttachmentStorage::DeltaLz4 => {
+                delta_decode(lz4_flex::decompress_size_prepended(&data)?, format)
+            }
+        };
+
+        Self::from_bytes(&data, format, texture_size)
+    }
+
+    /// Encodes the data of a tile for storage.
+    pub(crate) fn encode(
+        &self,
+        storage: AttachmentStorage,
+        format: AttachmentFormat,
+        texture_size: u32,
+    ) -> Result<Vec<u8>> {
+        Ok(match storage {
+            AttachmentStorage::Raw => self.bytes().to_vec(),
+            AttachmentStorage::Png => {
+                let image = match self.clone() {
+                    AttachmentData::Rgb8(data) => {
+                        let data = data.into_iter().flatten().collect_vec();
+                        Rgb8Image::from_raw(texture_size, texture_size, data)
+                            .map(DynamicImage::from)
+                    }
+                    AttachmentData::Rgba8(data) => {
+                        let data = data.into_iter().flatten().collect_vec();
+                        Rgba8Image::from_raw(texture_size, texture_size, data)
+                            .map(DynamicImage::from)
+                    }
+                    AttachmentData::R16(data) => {
+                        R16Image::from_raw(texture_size, texture_size, data).map(DynamicImage::from)
+                    }
+                    AttachmentData::Rg16(data) => {
+                        let data = data.into_iter().flatten().collect_vec();
+                        Rg16Image::from_raw(texture_size, texture_size, data)
+                            .map(DynamicImage::from)
+                    }
+                    _ => bail!("The {format:?} format can not be stored as PNG."),
+                };
+
+                let Some(image) = image else {
+                    bail!("The attachment data does not fit the texture size {texture_size}.");
+                };
+
+                let mut data = Vec::new();
+                image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+                data
+            }
+            AttachmentStorage::Lz4 => lz4_flex::compress_prepend_size(self.bytes()),
+            AttachmentStorage::DeltaLz4 => {
+                lz4_flex::compress_prepend_size(&delta_encode(self.bytes(), format))
+            }
+        })
+    }
+
     pub(crate) fn bytes(&self) -> &[u8] {
         match self {
+            AttachmentData::Rgb8(data) => cast_slice(data),
             AttachmentData::Rgba8(data) => cast_slice(data),
             AttachmentData::R16(data) => cast_slice(data),
             AttachmentData::Rg16(data) => cast_slice(data),
+            AttachmentData::R32F(data) => cast_slice(data),
+            AttachmentData::R16F(data) => cast_slice(data),
+            AttachmentData::R16Snorm(data) => cast_slice(data),
             AttachmentData::None => panic!("Attachment has no data."),
         }
     }
 
+    /// The bytes uploaded to the atlas texture.
+    pub(crate) fn texture_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            AttachmentData::Rgb8(data) => Cow::Owned(
+                data.iter()
+                    .flat_map(|&[r, g, b]| [r, g, b, u8::MAX])
+                    .collect(),
+            ),
+            data => Cow::Borrowed(data.bytes()),
+        }
+    }
+
     pub(crate) fn generate_mipmaps(&mut self, texture_size: u32, mip_level_count: u32) {
-        fn generate_mipmap_rgba8(
-            data: &mut Vec<[u8; 4]>,
+        fn generate_mipmap_unorm<T: Copy + Debug + Into<u64>, const N: usize>(
+            data: &mut Vec<[T; N]>,
             parent_size: usize,
             child_size: usize,
             start: usize,
+            from_value: impl Fn(u64) -> T,
         ) {
             for (child_y, child_x) in iproduct!(0..child_size, 0..child_size) {
-                let mut value = [0u64; 4];
+                let mut value = [0u64; N];
 
                 for i in 0..4 {
                     let parent_x = (child_x << 1) + (i >> 1);
@@ -156,10 +526,13 @@ impl AttachmentData {
 
                     let index = start + parent_y * parent_size + parent_x;
 
-                    iter::zip(&mut value, data[index]).for_each(|(value, v)| *value += v as u64);
+                    iter::zip(&mut value, data[index]).for_each(|(value, v)| *value += v.into());
                 }
 
-                let value = value.iter().map(|value| (value / 4) as u8).collect_array();
+                let value = value
+                    .iter()
+                    .map(|value| from_value(value / 4))
+                    .collect_array();
 
                 data.push(value);
             }
@@ -197,6 +570,42 @@ impl AttachmentData {
             }
         }
 
+        /// Averages the samples, which are not nodata.
+        /// If all samples are nodata, the mipmap is nodata as well.
+        fn generate_mipmap_r<T: Copy>(
+            data: &mut Vec<T>,
+            parent_size: usize,
+            child_size: usize,
+            start: usize,
+            to_value: impl Fn(T) -> f32,
+            from_value: impl Fn(f32) -> T,
+            nodata: Option<f32>,
+        ) {
+            for (child_y, child_x) in iproduct!(0..child_size, 0..child_size) {
+                let mut value = 0.0;
+                let mut count = 0;
+
+                for (parent_x, parent_y) in
+                    iproduct!(0..2, 0..2).map(|(x, y)| ((child_x << 1) + x, (child_y << 1) + y))
+                {
+                    let index = start + parent_y * parent_size + parent_x;
+                    let data = to_value(data[index]);
+
+                    if Some(data) != nodata {
+                        value += data;
+                        count += 1;
+                    }
+                }
+
+                let value = match nodata {
+                    Some(nodata) if count == 0 => nodata,
+                    _ => value / count as f32,
+                };
+
+                data.push(from_value(value));
+            }
+        }
+
         let mut start = 0;
         let mut parent_size = texture_size as usize;
 
@@ -204,12 +613,46 @@ impl AttachmentData {
             let child_size = parent_size >> 1;
 
             match self {
+                AttachmentData::Rgb8(data) => {
+                    generate_mipmap_unorm(data, parent_size, child_size, start, |v| v as u8)
+                }
                 AttachmentData::Rgba8(data) => {
-                    generate_mipmap_rgba8(data, parent_size, child_size, start)
+                    generate_mipmap_unorm(data, parent_size, child_size, start, |v| v as u8)
+                }
+                AttachmentData::Rg16(data) => {
+                    generate_mipmap_unorm(data, parent_size, child_size, start, |v| v as u16)
                 }
                 AttachmentData::R16(data) => {
                     generate_mipmap_r16(data, parent_size, child_size, start)
                 }
+                AttachmentData::R32F(data) => generate_mipmap_r(
+                    data,
+                    parent_size,
+                    child_size,
+                    start,
+                    |value| value,
+                    |value| value,
+                    None,
+                ),
+                AttachmentData::R16F(data) => generate_mipmap_r(
+                    data,
+                    parent_size,
+                    child_size,
+                    start,
+                    f16::to_f32,
+                    f16::from_f32,
+                    None,
+                ),
+                AttachmentData::R16Snorm(data) => generate_mipmap_r(
+                    data,
+                    parent_size,
+                    child_size,
+                    start,
+                    |value| (value as f32).max(-(i16::MAX as f32)),
+                    |value| value.round() as i16,
+                    // nodata is stored as the lowest value
+                    Some(-(i16::MAX as f32)),
+                ),
                 _ => {}
             }
 
@@ -218,6 +661,50 @@ impl AttachmentData {
         }
     }
 
+    /// The normalized value of the pixel at the index.
+    pub(crate) fn pixel(&self, index: usize) -> Vec4 {
+        match self {
+            AttachmentData::None => Vec4::splat(0.0),
+            AttachmentData::Rgb8(data) => {
+                let value = data[index];
+                Vec4::new(
+                    value[0] as f32 / u8::MAX as f32,
+                    value[1] as f32 / u8::MAX as f32,
+                    value[2] as f32 / u8::MAX as f32,
+                    1.0,
+                )
+            }
+            AttachmentData::Rgba8(data) => {
+                let value = data[index];
+                Vec4::new(
+                    value[0] as f32 / u8::MAX as f32,
+                    value[1] as f32 / u8::MAX as f32,
+                    value[2] as f32 / u8::MAX as f32,
+                    value[3] as f32 / u8::MAX as f32,
+                )
+            }
+            AttachmentData::R16(data) => {
+                let value = data[index];
+                Vec4::new(value as f32 / u16::MAX as f32, 0.0, 0.0, 0.0)
+            }
+            AttachmentData::Rg16(data) => {
+                let value = data[index];
+                Vec4::new(
+                    value[0] as f32 / u16::MAX as f32,
+                    value[1] as f32 / u16::MAX as f32,
+                    0.0,
+                    0.0,
+                )
+            }
+            AttachmentData::R32F(data) => Vec4::new(data[index], 0.0, 0.0, 0.0),
+            AttachmentData::R16F(data) => Vec4::new(data[index].to_f32(), 0.0, 0.0, 0.0),
+            AttachmentData::R16Snorm(data) => {
+                let value = data[index];
+                Vec4::new((value as f32 / i16::MAX as f32).max(-1.0), 0.0, 0.0, 0.0)
+            }
+        }
+    }
+
     pub(crate) fn sample(&self, uv: Vec2, size: u32) -> Vec4 {
         let uv = uv * size as f32 - 0.5;
 
@@ -229,31 +716,7 @@ impl AttachmentData {
         for (x, y) in iproduct!(0..2, 0..2) {
             let index = (uv.y + y) * size as i32 + (uv.x + x);
 
-            values[x as usize][y as usize] = match self {
-                AttachmentData::None => Vec4::splat(0.0),
-                AttachmentData::Rgba8(data) => {
-                    let value = data[index as usize];
-                    Vec4::new(
-                        value[0] as f32 / u8::MAX as f32,
-                        value[1] as f32 / u8::MAX as f32,
-                        value[2] as f32 / u8::MAX as f32,
-                        value[3] as f32 / u8::MAX as f32,
-                    )
-                }
-                AttachmentData::R16(data) => {
-                    let value = data[index as usize];
-                    Vec4::new(value as f32 / u16::MAX as f32, 0.0, 0.0, 0.0)
-                }
-                AttachmentData::Rg16(data) => {
-                    let value = data[index as usize];
-                    Vec4::new(
-                        value[0] as f32 / u16::MAX as f32,
-                        value[1] as f32 / u16::MAX as f32,
-                        0.0,
-                        0.0,
-                    )
-                }
-            };
+            values[x as usize][y as usize] = self.pixel(index as usize);
         }
 
         Vec4::lerp(
@@ -299,9 +762,134 @@ pub fn sample_height(
     tile_atlas: &TileAtlas,
     sample_world_position: DVec3,
 ) -> f32 {
-    f32::lerp(
-        tile_atlas.model.min_height,
-        tile_atlas.model.max_height,
-        sample_attachment(tile_tree, tile_atlas, 0, sample_world_position).x,
-    )
+    let (offset, scale) = tile_atlas.height_transform();
+
+    offset + scale * sample_attachment(tile_tree, tile_atlas, 0, sample_world_position).x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [AttachmentFormat; 7] = [
+        AttachmentFormat::Rgb8,
+        AttachmentFormat::Rgba8,
+        AttachmentFormat::R16,
+        AttachmentFormat::Rg16,
+        AttachmentFormat::R32F,
+        AttachmentFormat::R16F,
+        AttachmentFormat::R16Snorm,
+    ];
+
+    const STORAGES: [AttachmentStorage; 4] = [
+        AttachmentStorage::Raw,
+        AttachmentStorage::Png,
+        AttachmentStorage::Lz4,
+        AttachmentStorage::DeltaLz4,
+    ];
+
+    /// Creates smooth, but not constant bytes, similar to real terrain data.
+    fn test_bytes(texture_size: u32, format: AttachmentFormat) -> Vec<u8> {
+        let length = (texture_size * texture_size * format.pixel_size()) as usize;
+        let mut state = 0x2545_f491_u32;
+
+        (0..length)
+            .map(|i| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (i / 7) as u8 ^ (state >> 29) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn delta_round_trip() {
+        for format in FORMATS {
+            let data = test_bytes(8, format);
+            let decoded = delta_decode(delta_encode(&data, format), format);
+
+            assert_eq!(decoded, data, "{format:?}");
+        }
+    }
+
+    #[test]
+    fn storage_round_trip() {
+        let texture_size = 8;
+
+        for (format, storage) in iproduct!(FORMATS, STORAGES) {
+            if !storage.is_supported(format) {
+                continue;
+            }
+
+            let bytes = test_bytes(texture_size, format);
+            let data = AttachmentData::from_bytes(&bytes, format, texture_size).unwrap();
+
+            let encoded = data.encode(storage, format, texture_size).unwrap();
+            let decoded = AttachmentData::decode(encoded, storage, format, texture_size).unwrap();
+
+            assert_eq!(decoded.bytes(), bytes, "{format:?} {storage:?}");
+        }
+    }
+
+    #[test]
+    fn corrupt_tiles_fail() {
+        let texture_size = 8;
+
+        for (format, storage) in iproduct!(FORMATS, STORAGES) {
+            if !storage.is_supported(format) {
+                continue;
+            }
+
+            let mut bytes = test_bytes(texture_size, format);
+            bytes.truncate(bytes.len() - 1);
+
+            assert!(AttachmentData::from_bytes(&bytes, format, texture_size).is_err());
+
+            let data = AttachmentData::from_bytes(&test_bytes(4, format), format, 4).unwrap();
+            let encoded = data.encode(storage, format, 4).unwrap();
+
+            assert!(
+                AttachmentData::decode(encoded, storage, format, texture_size).is_err(),
+                "{format:?} {storage:?}"
+            );
+        }
+
+        let data = AttachmentData::from_bytes(
+            &test_bytes(8, AttachmentFormat::Rg16),
+            AttachmentFormat::Rg16,
+            8,
+        )
+        .unwrap();
+        let encoded = data
+            .encode(AttachmentStorage::Png, AttachmentFormat::Rg16, 8)
+            .unwrap();
+
+        // the bytes of a RG16 tile match the size of a RGBA8 tile
+        assert!(AttachmentData::decode(
+            encoded,
+            AttachmentStorage::Png,
+            AttachmentFormat::Rgba8,
+            8
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn height_mipmaps() {
+        // zero is a valid height of the signed and float formats
+        let mut data = AttachmentData::R32F(vec![0.0, 0.0, 4.0, 0.0]);
+        data.generate_mipmaps(2, 2);
+        assert_eq!(data.bytes(), cast_slice::<_, u8>(&[0.0f32, 0.0, 4.0, 0.0, 1.0]));
+
+        // nodata is skipped, unless all samples are nodata
+        let mut data = AttachmentData::R16Snorm(vec![i16::MIN, 0, 300, -i16::MAX]);
+        data.generate_mipmaps(2, 2);
+        assert_eq!(
+            data.bytes(),
+            cast_slice::<_, u8>(&[i16::MIN, 0, 300, -i16::MAX, 150])
+        );
+
+        let mut data = AttachmentData::R16Snorm(vec![-i16::MAX; 4]);
+        data.generate_mipmaps(2, 2);
+        assert_eq!(data.bytes(), cast_slice::<_, u8>(&[-i16::MAX; 5]));
+    }
 }
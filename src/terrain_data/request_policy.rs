@@ -0,0 +1,200 @@
+use crate::math::{Coordinate, TerrainModel, TileCoordinate};
+use anyhow::{ensure, Result};
+use bevy::{
+    math::{DVec2, DVec3},
+    render::primitives::{Frustum, Sphere},
+};
+use std::sync::Arc;
+
+/// A tile of the [`TileTree`](super::tile_tree::TileTree), for which the [`RequestPolicy`]
+/// decides, whether it should be loaded.
+#[derive(Clone, Copy, Debug)]
+pub struct TileRequest {
+    pub coordinate: TileCoordinate,
+    /// The distance between the view and the closest point of the tile.
+    pub distance: f64,
+    /// The load distance of the LOD of the tile.
+    pub load_distance: f64,
+}
+
+/// The state of the view during the current update of its
+/// [`TileTree`](super::tile_tree::TileTree).
+pub struct RequestContext<'a> {
+    pub model: &'a TerrainModel,
+    pub view_position: DVec3,
+    /// The height of the view above the approximated terrain surface.
+    pub altitude: f64,
+    /// The approximated height of the terrain below the view.
+    pub approximate_height: f32,
+    /// The amount of pixels an object of size one covers at distance one, if the view is a
+    /// camera with a perspective projection.
+    pub projection_scale: Option<f64>,
+    pub(crate) frustum: Option<&'a Frustum>,
+    /// The position of the view in the space of the frustum.
+    pub(crate) frustum_offset: DVec3,
+}
+
+impl<'a> RequestContext<'a> {
+    fn tile_position(&self, tile: TileCoordinate, offset: DVec2) -> DVec3 {
+        let tile_xy = DVec2::new(tile.x as f64, tile.y as f64);
+        let uv = (tile_xy + offset) / TileCoordinate::count(tile.lod) as f64;
+
+        Coordinate::new(tile.side, uv).world_position(self.model, self.approximate_height)
+    }
+
+    /// Computes the length of the diagonal of the tile.
+    pub fn tile_size(&self, tile: TileCoordinate) -> f64 {
+        self.tile_position(tile, DVec2::ZERO)
+            .distance(self.tile_position(tile, DVec2::ONE))
+    }
+
+    /// Computes the bounding sphere (center and radius) of the tile, including the
+    /// height range of the terrain.
+    pub fn tile_bounds(&self, tile: TileCoordinate) -> (DVec3, f64) {
+        let center = self.tile_position(tile, DVec2::splat(0.5));
+        let radius = [DVec2::ZERO, DVec2::X, DVec2::Y, DVec2::ONE]
+            .into_iter()
+            .map(|corner| self.tile_position(tile, corner).distance(center))
+            .fold(0.0, f64::max);
+        let height_range = (self.model.max_height - self.model.min_height) as f64;
+
+        (center, radius + height_range)
+    }
+
+    /// Whether the sphere intersects the frustum of the view.
+    /// Returns `None`, if the view has no frustum.
+    pub fn intersects_frustum(&self, center: DVec3, radius: f64) -> Option<bool> {
+        let frustum = self.frustum?;
+
+        let sphere = Sphere {
+            center: (center - self.view_position + self.frustum_offset)
+                .as_vec3()
+                .into(),
+            radius: radius as f32,
+        };
+
+        Some(frustum.intersects_sphere(&sphere, false))
+    }
+}
+
+/// Decides which tiles a [`TileTree`](super::tile_tree::TileTree) requests from the
+/// [`TileAtlas`](super::tile_atlas::TileAtlas).
+///
+/// The tiles of the coarsest LOD are always requested, since they serve as the fallback
+/// for all other tiles.
+///
+/// Implement this trait to tailor the loaded tiles to your application.
+pub trait RequestPolicy: Send + Sync + 'static {
+    /// Whether the tile should be loaded.
+    fn is_requested(&self, tile: &TileRequest, context: &RequestContext) -> bool;
+}
+
+/// Requests all tiles within the load distance of the view.
+///
+/// This is the default policy.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DistancePolicy;
+
+impl RequestPolicy for DistancePolicy {
+    fn is_requested(&self, tile: &TileRequest, _context: &RequestContext) -> bool {
+        tile.distance < tile.load_distance
+    }
+}
+
+/// Only requests the tiles inside the view frustum, which are requested by the inner policy.
+///
+/// The `margin` enlarges the tiles by a fraction of the load distance of their LOD, so that
+/// tiles next to the frustum are already loaded, when the view turns.
+/// Views without a frustum (e.g. [`TerrainProbe`](crate::terrain_view::TerrainProbe)s)
+/// only use the inner policy.
+#[derive(Clone)]
+pub struct FrustumPolicy {
+    pub margin: f64,
+    pub policy: Arc<dyn RequestPolicy>,
+}
+
+impl FrustumPolicy {
+    pub fn new(margin: f64, policy: impl RequestPolicy) -> Self {
+        Self {
+            margin,
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl Default for FrustumPolicy {
+    fn default() -> Self {
+        Self::new(0.5, DistancePolicy)
+    }
+}
+
+impl RequestPolicy for FrustumPolicy {
+    fn is_requested(&self, tile: &TileRequest, context: &RequestContext) -> bool {
+        if !self.policy.is_requested(tile, context) {
+            return false;
+        }
+
+        let (center, radius) = context.tile_bounds(tile.coordinate);
+        let radius = radius + self.margin * tile.load_distance;
+
+        context.intersects_frustum(center, radius).unwrap_or(true)
+    }
+}
+
+/// Requests the tiles within a load distance, which shrinks as the view rises above the terrain.
+///
+/// The load distance is halved at an altitude of `altitude_scale` (in world units),
+/// quartered at three times this altitude and so on.
+/// This skips one LOD each time the altitude doubles, which suits flying views.
+#[derive(Clone, Copy, Debug)]
+pub struct AltitudePolicy {
+    pub altitude_scale: f64,
+}
+
+impl AltitudePolicy {
+    pub fn new(altitude_scale: f64) -> Result<Self> {
+        ensure!(
+            altitude_scale > 0.0,
+            "The altitude scale has to be positive, but is {altitude_scale}."
+        );
+
+        Ok(Self { altitude_scale })
+    }
+}
+
+impl RequestPolicy for AltitudePolicy {
+    fn is_requested(&self, tile: &TileRequest, context: &RequestContext) -> bool {
+        let scale = 1.0 / (1.0 + context.altitude / self.altitude_scale);
+
+        tile.distance < tile.load_distance * scale
+    }
+}
+
+/// Requests the tiles, whose projection on the screen is larger than `min_size` pixels.
+///
+/// Since a tile replaces its parent, a good value is half the texture size of the attachments,
+/// which loads a tile once the texels of its parent become larger than a pixel.
+/// Views without a perspective projection fall back to the [`DistancePolicy`].
+#[derive(Clone, Copy, Debug)]
+pub struct ScreenSizePolicy {
+    pub min_size: f64,
+}
+
+impl ScreenSizePolicy {
+    pub fn new(min_size: f64) -> Self {
+        Self { min_size }
+    }
+}
+
+impl RequestPolicy for ScreenSizePolicy {
+    fn is_requested(&self, tile: &TileRequest, context: &RequestContext) -> bool {
+        let Some(projection_scale) = context.projection_scale else {
+            return DistancePolicy.is_requested(tile, context);
+        };
+
+        let size =
+            context.tile_size(tile.coordinate) * projection_scale / tile.distance.max(f64::EPSILON);
+
+        size > self.min_size
+    }
+}
@@ -0,0 +1,91 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// The upper bounds of the buckets of the [`LatencyHistogram`] in milliseconds.
+/// The last bucket contains all larger latencies.
+pub const LATENCY_BUCKETS: [u64; 10] = [1, 2, 5, 10, 20, 50, 100, 200, 500, 1000];
+
+/// A histogram of the time it took to load tile attachments.
+#[derive(Clone, Debug, Default)]
+pub struct LatencyHistogram {
+    /// The count of latencies per bucket, where bucket `i` contains the latencies
+    /// below `LATENCY_BUCKETS[i]` milliseconds.
+    pub counts: [u64; LATENCY_BUCKETS.len() + 1],
+    /// The sum of all recorded latencies.
+    pub total: Duration,
+}
+
+impl LatencyHistogram {
+    pub(crate) fn record(&mut self, latency: Duration) {
+        let millis = latency.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| millis < bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.counts[bucket] += 1;
+        self.total += latency;
+    }
+
+    /// The count of recorded latencies.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// The average of all recorded latencies.
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => self.total / count as u32,
+        }
+    }
+
+    /// The upper bound of the bucket containing the given quantile (0 to 1) of all latencies.
+    /// Returns `None` if the quantile lies inside the last, unbounded bucket.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        let target = (quantile.clamp(0.0, 1.0) * self.count() as f64).ceil() as u64;
+        let mut count = 0;
+
+        for (bucket, &bound) in LATENCY_BUCKETS.iter().enumerate() {
+            count += self.counts[bucket];
+
+            if count >= target {
+                return Some(Duration::from_millis(bound));
+            }
+        }
+
+        None
+    }
+}
+
+/// The streaming statistics of the [`TileAtlas`](super::tile_atlas::TileAtlas) of a terrain.
+///
+/// It is updated every frame by the tile atlas of the same entity.
+/// The tile counts refer to the current frame, while the other statistics accumulate
+/// since the terrain was spawned.
+#[derive(Component, Clone, Debug, Default)]
+pub struct TileAtlasStatistics {
+    /// The count of requested tiles, which are loaded.
+    pub loaded_tiles: u32,
+    /// The count of tiles, which are loading.
+    pub loading_tiles: u32,
+    /// The count of loaded tiles, which are not requested anymore, but kept until
+    /// their atlas index is reused.
+    pub cached_tiles: u32,
+    /// The count of atlas indices, which are not occupied by any tile.
+    pub free_tiles: u32,
+    /// The count of tile attachments, which are waiting to be loaded.
+    pub pending_loads: u32,
+    /// The count of requested tiles, which are waiting for an atlas index.
+    pub deferred_tiles: u32,
+    /// The count of cached tiles, whose atlas index has been reused.
+    pub cache_evictions: u64,
+    /// The count of requested tiles, which have been evicted, because the atlas was full.
+    pub pressure_evictions: u64,
+    /// The count of tile attachments, which have failed to load.
+    pub failed_loads: u64,
+    /// The amount of bytes the tile data of each attachment occupies in memory.
+    pub attachment_bytes: Vec<u64>,
+    /// The time it took to load the tile attachments.
+    pub load_latency: LatencyHistogram,
+}
@@ -1,32 +1,41 @@
 use crate::{
-    formats::TC,
-    math::{TerrainModel, TileCoordinate},
-    prelude::{AttachmentConfig, AttachmentFormat},
-    terrain::TerrainConfig,
+    formats::TerrainManifest,
+    math::{Coordinate, TerrainModel, TileCoordinate},
+    prelude::{AttachmentConfig, AttachmentFormat, AttachmentStorage},
+    terrain::{AttachmentFootprint, TerrainConfig},
     terrain_data::{
+        block_compression::BlockCompression,
+        statistics::TileAtlasStatistics,
+        tile_source::TileSource,
         tile_tree::{TileLookup, TileTree, TileTreeEntry},
         AttachmentData, INVALID_ATLAS_INDEX, INVALID_LOD,
     },
     terrain_view::TerrainViewComponents,
 };
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use bevy::{
+    math::{DVec2, DVec3},
     prelude::*,
-    render::render_resource::*,
+    render::{render_resource::*, renderer::RenderDevice},
     tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
     utils::{HashMap, HashSet},
 };
-use image::{io::Reader, DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
-use itertools::Itertools;
-use std::{collections::VecDeque, fs, mem, ops::DerefMut};
+use image::{ImageBuffer, Luma, LumaA, Rgb, Rgba};
+use itertools::{iproduct, Itertools};
+use std::{
+    collections::VecDeque,
+    mem,
+    ops::{DerefMut, Range},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub type Rgb8Image = ImageBuffer<Rgb<u8>, Vec<u8>>;
 pub type Rgba8Image = ImageBuffer<Rgba<u8>, Vec<u8>>;
 pub type R16Image = ImageBuffer<Luma<u16>, Vec<u16>>;
 pub type Rg16Image = ImageBuffer<LumaA<u16>, Vec<u16>>;
 
-const STORE_PNG: bool = false;
-
 #[derive(Copy, Clone, Debug, Default, ShaderType)]
 pub struct AtlasTile {
     pub(crate) coordinate: TileCoordinate,
@@ -70,79 +79,106 @@ pub struct AtlasTileAttachment {
 pub(crate) struct AtlasTileAttachmentWithData {
     pub(crate) tile: AtlasTileAttachment,
     pub(crate) data: AttachmentData,
+    /// The block compressed data of all mip levels, if the attachment is block compressed.
+    pub(crate) blocks: Option<Vec<u8>>,
     pub(crate) texture_size: u32,
 }
 
 impl AtlasTileAttachmentWithData {
-    pub(crate) fn start_saving(self, path: String) -> Task<AtlasTileAttachment> {
+    pub(crate) fn start_saving(
+        self,
+        source: Arc<dyn TileSource>,
+        config: AttachmentConfig,
+    ) -> Task<AtlasTileAttachment> {
         AsyncComputeTaskPool::get().spawn(async move {
-            if STORE_PNG {
-                let path = self.tile.coordinate.path(&path, "png");
-
-                let image = match self.data {
-                    AttachmentData::Rgba8(data) => {
-                        let data = data.into_iter().flatten().collect_vec();
-                        DynamicImage::from(
-                            Rgba8Image::from_raw(self.texture_size, self.texture_size, data)
-                                .unwrap(),
-                        )
-                    }
-                    AttachmentData::R16(data) => DynamicImage::from(
-                        R16Image::from_raw(self.texture_size, self.texture_size, data).unwrap(),
-                    ),
-                    AttachmentData::Rg16(data) => {
-                        let data = data.into_iter().flatten().collect_vec();
-                        DynamicImage::from(
-                            Rg16Image::from_raw(self.texture_size, self.texture_size, data)
-                                .unwrap(),
-                        )
-                    }
-                    AttachmentData::None => panic!("Attachment has not data."),
-                };
-
-                image.save(&path).unwrap();
-
-                println!("Finished saving tile: {path}");
-            } else {
-                let path = self.tile.coordinate.path(&path, "bin");
-
-                fs::write(path, self.data.bytes()).unwrap();
-
-                // println!("Finished saving tile: {path}");
+            let bytes = self
+                .data
+                .encode(config.storage, config.format, self.texture_size)
+                .unwrap();
+
+            source
+                .save_tile(
+                    &config.name,
+                    self.tile.coordinate,
+                    config.storage.extension(),
+                    &bytes,
+                )
+                .await
+                .unwrap();
+
+            // the blocks of all mip levels are stored alongside the tile,
+            // so that they do not have to be encoded each time the tile is loaded
+            if config.block_compression != BlockCompression::None {
+                let mut data = self.data;
+                data.generate_mipmaps(config.texture_size, config.mip_level_count);
+
+                let blocks = config
+                    .block_compression
+                    .encode(&data, config.texture_size, config.mip_level_count)
+                    .unwrap();
+
+                source
+                    .save_tile(
+                        &config.name,
+                        self.tile.coordinate,
+                        config.block_compression.extension(),
+                        &blocks,
+                    )
+                    .await
+                    .unwrap();
             }
 
+            // println!("Finished saving tile: {}", self.tile.coordinate);
+
             self.tile
         })
     }
 
     pub(crate) fn start_loading(
         tile: AtlasTileAttachment,
-        path: String,
-        texture_size: u32,
-        format: AttachmentFormat,
-        mip_level_count: u32,
+        source: Arc<dyn TileSource>,
+        config: AttachmentConfig,
     ) -> Task<Result<Self>> {
         AsyncComputeTaskPool::get().spawn(async move {
-            let mut data = if STORE_PNG {
-                let path = tile.coordinate.path(&path, "png");
-
-                let mut reader = Reader::open(path)?;
-                reader.no_limits();
-                let image = reader.decode().unwrap();
-                AttachmentData::from_bytes(image.as_bytes(), format)
-            } else {
-                let path = tile.coordinate.path(&path, "bin");
+            let AttachmentConfig {
+                ref name,
+                texture_size,
+                mip_level_count,
+                format,
+                storage,
+                block_compression,
+                ..
+            } = config;
+
+            let bytes = source
+                .load_tile(name, tile.coordinate, storage.extension())
+                .await?;
+            let mut data = AttachmentData::decode(bytes, storage, format, texture_size)?;
 
-                let bytes = fs::read(path)?;
+            data.generate_mipmaps(texture_size, mip_level_count);
 
-                AttachmentData::from_bytes(&bytes, format)
+            let blocks = match block_compression {
+                BlockCompression::None => None,
+                _ => {
+                    let blocks = source
+                        .load_tile(name, tile.coordinate, block_compression.extension())
+                        .await?;
+
+                    ensure!(
+                        blocks.len() as u64 == config.gpu_tile_size(),
+                        "The {block_compression:?} blocks of the tile have {} bytes, but {} bytes are required.",
+                        blocks.len(),
+                        config.gpu_tile_size()
+                    );
+
+                    Some(blocks)
+                }
             };
 
-            data.generate_mipmaps(texture_size, mip_level_count);
-
             Ok(Self {
                 tile,
                 data,
+                blocks,
                 texture_size: 0,
             })
         })
@@ -160,18 +196,51 @@ pub struct AtlasAttachment {
     offset: f32,
     pub(crate) mip_level_count: u32,
     pub(crate) format: AttachmentFormat,
+    storage: AttachmentStorage,
+    pub(crate) block_compression: BlockCompression,
     pub(crate) data: Vec<AttachmentData>,
+    source: Arc<dyn TileSource>,
 
     pub(crate) saving_tiles: Vec<Task<AtlasTileAttachment>>,
-    pub(crate) loading_tiles: Vec<Task<Result<AtlasTileAttachmentWithData>>>,
+    pub(crate) loading_tiles: Vec<(
+        AtlasTileAttachment,
+        Instant,
+        Task<Result<AtlasTileAttachmentWithData>>,
+    )>,
+    /// The loaded tiles, which wait for the upload budget of a following frame.
+    /// They are registered as loaded, once they are uploaded.
+    budgeted_tiles: VecDeque<AtlasTileAttachmentWithData>,
     pub(crate) uploading_tiles: Vec<AtlasTileAttachmentWithData>,
     pub(crate) downloading_tiles: Vec<Task<AtlasTileAttachmentWithData>>,
 }
 
 impl AtlasAttachment {
-    fn new(config: &AttachmentConfig, tile_atlas_size: u32, path: &str) -> Self {
+    fn new(
+        config: &AttachmentConfig,
+        is_height: bool,
+        tile_atlas_size: u32,
+        path: &str,
+        source: Arc<dyn TileSource>,
+    ) -> Self {
         let name = config.name.clone();
         let path = format!("assets/{path}/data/{name}");
+
+        let block_compression = match Self::validate_block_compression(config, is_height) {
+            Ok(()) => config.block_compression,
+            Err(error) => {
+                error!("Falling back to uncompressed tiles for the attachment {name}: {error}");
+                BlockCompression::None
+            }
+        };
+
+        if !config.storage.is_supported(config.format) {
+            // the tiles will fail to load and are replaced by their parents
+            error!(
+                "The attachment {name} with the {:?} format can not be stored as {:?}.",
+                config.format, config.storage
+            );
+        }
+
         let center_size = config.texture_size - 2 * config.border_size;
 
         Self {
@@ -184,29 +253,95 @@ impl AtlasAttachment {
             offset: config.border_size as f32 / config.texture_size as f32,
             mip_level_count: config.mip_level_count,
             format: config.format,
+            storage: config.storage,
+            block_compression,
             data: vec![AttachmentData::None; tile_atlas_size as usize],
+            source,
             saving_tiles: default(),
             loading_tiles: default(),
+            budgeted_tiles: default(),
             uploading_tiles: default(),
             downloading_tiles: default(),
         }
     }
 
+    fn validate_block_compression(config: &AttachmentConfig, is_height: bool) -> Result<()> {
+        if config.block_compression == BlockCompression::None {
+            return Ok(());
+        }
+
+        ensure!(
+            !is_height,
+            "The height attachment can not be block compressed, since the CPU samples the exact heights, while the GPU would sample the quantized blocks."
+        );
+        ensure!(
+            config.block_compression.is_supported(config.format),
+            "The {:?} format can not be compressed with {:?}.",
+            config.format,
+            config.block_compression
+        );
+        ensure!(
+            config.texture_size.is_multiple_of(4),
+            "The texture size of block compressed attachments has to be a multiple of four."
+        );
+
+        Ok(())
+    }
+
+    fn config(&self) -> AttachmentConfig {
+        AttachmentConfig {
+            name: self.name.clone(),
+            texture_size: self.texture_size,
+            border_size: self.border_size,
+            mip_level_count: self.mip_level_count,
+            format: self.format,
+            storage: self.storage,
+            block_compression: self.block_compression,
+        }
+    }
+
     fn update(&mut self, atlas_state: &mut TileAtlasState) {
-        self.loading_tiles.retain_mut(|tile| {
-            future::block_on(future::poll_once(tile)).map_or(true, |tile| {
-                if let Ok(tile) = tile {
-                    atlas_state.loaded_tile_attachment(tile.tile);
-                    self.uploading_tiles.push(tile.clone());
-                    self.data[tile.tile.atlas_index as usize] = tile.data;
-                } else {
-                    atlas_state.load_slots += 1;
+        self.loading_tiles.retain_mut(|(tile, start, task)| {
+            future::block_on(future::poll_once(task)).map_or(true, |result| {
+                match result {
+                    Ok(tile) => {
+                        atlas_state.statistics.load_latency.record(start.elapsed());
+                        self.budgeted_tiles.push_back(tile);
+                    }
+                    Err(error) => atlas_state.failed_tile_attachment(*tile, &self.name, error),
                 }
 
                 false
             })
         });
 
+        // the remaining tiles are uploaded in the following frames
+        let count = match atlas_state.settings.upload_budget {
+            Some(budget) => {
+                let mut budget = budget / atlas_state.attachment_count.max(1) as u64;
+
+                self.budgeted_tiles
+                    .iter()
+                    .enumerate()
+                    .take_while(|&(index, tile)| {
+                        let size = self.upload_size(tile);
+                        let fits = index == 0 || size <= budget;
+                        budget = budget.saturating_sub(size);
+                        fits
+                    })
+                    .count()
+            }
+            None => self.budgeted_tiles.len(),
+        };
+
+        for tile in self.budgeted_tiles.drain(..count) {
+            // tiles, which have been evicted or failed meanwhile, are discarded
+            if atlas_state.loaded_tile_attachment(tile.tile) {
+                self.uploading_tiles.push(tile.clone());
+                self.data[tile.tile.atlas_index as usize] = tile.data;
+            }
+        }
+
         self.downloading_tiles.retain_mut(|tile| {
             future::block_on(future::poll_once(tile)).map_or(true, |tile| {
                 atlas_state.downloaded_tile_attachment(tile.tile);
@@ -223,16 +358,23 @@ impl AtlasAttachment {
         });
     }
 
+    /// The amount of bytes the tile occupies, when uploaded to the GPU.
+    fn upload_size(&self, tile: &AtlasTileAttachmentWithData) -> u64 {
+        tile.blocks.as_ref().map_or_else(
+            || {
+                (tile.data.bytes().len() / self.format.pixel_size() as usize
+                    * self.format.texture_pixel_size() as usize) as u64
+            },
+            |blocks| blocks.len() as u64,
+        )
+    }
+
     fn load(&mut self, tile: AtlasTileAttachment) {
-        // Todo: build customizable loader abstraction
-        self.loading_tiles
-            .push(AtlasTileAttachmentWithData::start_loading(
-                tile,
-                self.path.clone(),
-                self.texture_size,
-                self.format,
-                self.mip_level_count,
-            ));
+        self.loading_tiles.push((
+            tile,
+            Instant::now(),
+            AtlasTileAttachmentWithData::start_loading(tile, self.source.clone(), self.config()),
+        ));
     }
 
     fn save(&mut self, tile: AtlasTileAttachment) {
@@ -240,9 +382,10 @@ impl AtlasAttachment {
             AtlasTileAttachmentWithData {
                 tile: tile,
                 data: self.data[tile.atlas_index as usize].clone(),
+                blocks: None,
                 texture_size: self.texture_size,
             }
-            .start_saving(self.path.clone()),
+            .start_saving(self.source.clone(), self.config()),
         );
     }
 
@@ -279,23 +422,162 @@ struct TileState {
     requests: u32,
 }
 
+/// The settings, which limit how many tiles a [`TileAtlas`] streams concurrently.
+///
+/// Lower limits reduce hitches on low-end machines, while higher limits speed up
+/// the preprocessing on machines with fast storage.
+#[derive(Clone, Copy, Debug)]
+pub struct StreamingSettings {
+    /// The maximum count of tile attachments, which are loaded concurrently.
+    pub load_slots: u32,
+    /// The maximum count of tile attachments, which are saved concurrently.
+    pub save_slots: u32,
+    /// The maximum count of tile attachments, which are downloaded from the GPU concurrently.
+    pub download_slots: u32,
+    /// The maximum count of tile attachments, which are written by the preprocessing per frame.
+    /// This is only applied when the GPU atlas is created, since it determines its buffer sizes.
+    pub atlas_write_slots: u32,
+    /// The maximum amount of bytes uploaded to the GPU per frame, which is split evenly
+    /// between the attachments. At least one tile attachment is uploaded per attachment and frame.
+    /// Tiles, which exceed the budget, stay loading until they are uploaded in a following frame.
+    /// Otherwise, all loaded tiles are uploaded immediately.
+    pub upload_budget: Option<u64>,
+    /// The count of times a tile attachment is reloaded after failing to load.
+    /// Afterwards, the tile is skipped and its parent is used instead.
+    pub max_load_retries: u32,
+    /// The delay before the first reload of a failed tile attachment,
+    /// which is doubled after each failed attempt.
+    pub retry_backoff: Duration,
+}
+
+impl Default for StreamingSettings {
+    fn default() -> Self {
+        Self {
+            load_slots: 64,
+            save_slots: 64,
+            download_slots: 128,
+            atlas_write_slots: 32,
+            upload_budget: None,
+            max_load_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Sent every frame, in which the [`TileAtlas`] of the terrain has run out of atlas indices.
+///
+/// The requests that could not be satisfied are deferred, until indices become available,
+/// and the least important requested tiles are evicted in favour of more important ones.
+/// Meanwhile, the affected areas are rendered with lower resolution data.
+/// Reducing the `load_distance` of the views or increasing the `atlas_size` relieves the pressure.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TileAtlasExhausted {
+    /// The terrain entity of the tile atlas.
+    pub terrain: Entity,
+    /// The count of requested tiles, which have not been assigned an atlas index.
+    pub deferred_tiles: u32,
+    /// The count of requested tiles, which have been evicted this frame.
+    pub evicted_tiles: u32,
+}
+
+/// The stage of the lifecycle of a tile inside a [`TileAtlas`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileEventKind {
+    /// The tile has been assigned an atlas index and started loading.
+    Requested,
+    /// All attachments of the tile have been loaded and its data can be accessed.
+    Loaded,
+    /// The data of the loaded tile has been removed from the atlas.
+    Evicted,
+    /// The tile could not be loaded and is replaced by its parent.
+    Failed,
+    /// The data of an attachment of the loaded tile has changed in the tile source
+    /// and has been reloaded.
+    Reloaded,
+}
+
+/// Sent whenever a tile of a [`TileAtlas`] advances in its lifecycle.
+///
+/// This can be used to build content on top of the terrain data, like spawning props
+/// once a tile is loaded and despawning them once it is evicted.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TileEvent {
+    /// The terrain entity of the tile atlas.
+    pub terrain: Entity,
+    pub coordinate: TileCoordinate,
+    /// The index of the tile inside the atlas.
+    pub atlas_index: u32,
+    pub kind: TileEventKind,
+}
+
+/// Sent whenever an attachment of a tile of a [`TileAtlas`] fails to load.
+#[derive(Event, Clone, Debug)]
+pub struct TileLoadError {
+    /// The terrain entity of the tile atlas.
+    pub terrain: Entity,
+    pub coordinate: TileCoordinate,
+    /// The name of the attachment.
+    pub attachment: String,
+    /// The description of the error, including the location of the tile if available.
+    pub error: String,
+    /// The count of failed attempts to load the attachment.
+    pub attempt: u32,
+    /// Whether the tile has been given up on, in which case its parent is used instead.
+    pub permanent: bool,
+}
+
+/// A handle to a set of tiles pinned inside a [`TileAtlas`].
+///
+/// The tiles stay requested until the handle is passed to [`TileAtlas::unpin`].
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct TilePin(u32);
+
 pub(crate) struct TileAtlasState {
     tile_states: HashMap<TileCoordinate, TileState>,
     unused_tiles: VecDeque<AtlasTile>,
+    /// The requested tiles, which are waiting for an atlas index, and their request counts.
+    deferred_tiles: HashMap<TileCoordinate, u32>,
     pub(crate) existing_tiles: HashSet<TileCoordinate>,
+    /// The tiles, which have failed to load repeatedly, and their request counts.
+    /// They are not loaded anymore, until their data changes.
+    failed_tiles: HashMap<TileCoordinate, u32>,
+    /// The count of failed attempts to load each attachment of the tiles.
+    load_attempts: HashMap<(TileCoordinate, u32), u32>,
+    /// The tile attachments, which are reloaded once their backoff has passed.
+    retries: Vec<(Instant, AtlasTileAttachment)>,
+    /// The tiles of each pin.
+    pins: HashMap<u32, Vec<TileCoordinate>>,
+    /// The count of pins of each pinned tile.
+    pinned_tiles: HashMap<TileCoordinate, u32>,
+    /// The attachments of loaded tiles, which are reloaded, since they have changed.
+    reloading: HashSet<(TileCoordinate, u32)>,
+    /// The attachments, which have changed again during their reload,
+    /// and are reloaded once more after it has finished.
+    dirty: HashSet<(TileCoordinate, u32)>,
+    /// The attachments, whose running reload has been cancelled, since their tile was removed.
+    /// The next load of each of them, which finishes, is discarded.
+    cancelled_reloads: HashSet<(TileCoordinate, u32)>,
+    next_pin: u32,
 
     attachment_count: u32,
 
+    pub(crate) settings: StreamingSettings,
+
     to_load: VecDeque<AtlasTileAttachment>,
-    load_slots: u32,
+    /// The count of tile attachments, which are currently loading.
+    loading: u32,
     to_save: VecDeque<AtlasTileAttachment>,
-    pub(crate) save_slots: u32,
-    pub(crate) max_save_slots: u32,
-
-    pub(crate) download_slots: u32,
-    pub(crate) max_download_slots: u32,
-
-    pub(crate) max_atlas_write_slots: u32,
+    /// The count of tile attachments, which are currently saving.
+    pub(crate) saving: u32,
+    /// The count of tile attachments, which are currently downloading.
+    pub(crate) downloading: u32,
+
+    /// The accumulated statistics, the current tile counts are filled in on demand.
+    statistics: TileAtlasStatistics,
+    /// The lifecycle events of this frame, which are sent by the update system.
+    events: Vec<(AtlasTile, TileEventKind)>,
+    /// The load errors of this frame, which are sent by the update system.
+    load_errors: Vec<TileLoadError>,
 }
 
 impl TileAtlasState {
@@ -303,6 +585,7 @@ impl TileAtlasState {
         atlas_size: u32,
         attachment_count: u32,
         existing_tiles: HashSet<TileCoordinate>,
+        settings: StreamingSettings,
     ) -> Self {
         let unused_tiles = (0..atlas_size)
             .map(|atlas_index| AtlasTile::new(TileCoordinate::INVALID, atlas_index))
@@ -311,59 +594,458 @@ impl TileAtlasState {
         Self {
             tile_states: default(),
             unused_tiles,
+            deferred_tiles: default(),
             existing_tiles,
+            failed_tiles: default(),
+            load_attempts: default(),
+            retries: default(),
+            pins: default(),
+            pinned_tiles: default(),
+            reloading: default(),
+            dirty: default(),
+            cancelled_reloads: default(),
+            next_pin: 0,
             attachment_count,
+            settings,
             to_save: default(),
             to_load: default(),
-            save_slots: 64,
-            max_save_slots: 64,
-            load_slots: 64,
-            download_slots: 128,
-            max_download_slots: 128,
-            max_atlas_write_slots: 32,
+            loading: 0,
+            saving: 0,
+            downloading: 0,
+            statistics: default(),
+            events: default(),
+            load_errors: default(),
         }
     }
 
-    fn update(&mut self, attachments: &mut [AtlasAttachment]) {
-        while self.save_slots > 0 {
+    fn update(
+        &mut self,
+        attachments: &mut [AtlasAttachment],
+        priority: impl Fn(TileCoordinate) -> f64,
+    ) -> u32 {
+        // pinned tiles are loaded before all other tiles
+        let pinned_tiles = mem::take(&mut self.pinned_tiles);
+        let priority = |tile| {
+            if pinned_tiles.contains_key(&tile) {
+                0.0
+            } else {
+                priority(tile)
+            }
+        };
+
+        let now = Instant::now();
+
+        let (due, retries) = mem::take(&mut self.retries)
+            .into_iter()
+            .partition::<Vec<_>, _>(|&(retry_at, _)| retry_at <= now);
+        self.retries = retries;
+
+        for (_, tile) in due {
+            if self.is_loading(tile) {
+                self.to_load.push_back(tile);
+            }
+        }
+
+        let evicted_tiles = self.allocate_deferred_tiles(&priority, &pinned_tiles);
+        self.prioritize(priority);
+        self.pinned_tiles = pinned_tiles;
+
+        while self.saving < self.settings.save_slots {
             if let Some(tile) = self.to_save.pop_front() {
                 attachments[tile.attachment_index as usize].save(tile);
-                self.save_slots -= 1;
+                self.saving += 1;
             } else {
                 break;
             }
         }
 
-        while self.load_slots > 0 {
+        while self.loading < self.settings.load_slots {
             if let Some(tile) = self.to_load.pop_front() {
                 attachments[tile.attachment_index as usize].load(tile);
-                self.load_slots -= 1;
+                self.loading += 1;
             } else {
                 break;
             }
         }
+
+        evicted_tiles
+    }
+
+    /// Assigns the free atlas indices to the most important deferred tiles.
+    ///
+    /// If the atlas is full, requested tiles, which are less important than the deferred ones,
+    /// are evicted and deferred themselves.
+    /// Returns the count of evicted tiles.
+    fn allocate_deferred_tiles(
+        &mut self,
+        priority: &impl Fn(TileCoordinate) -> f64,
+        pinned_tiles: &HashMap<TileCoordinate, u32>,
+    ) -> u32 {
+        if self.deferred_tiles.is_empty() {
+            return 0;
+        }
+
+        let mut deferred_tiles = self
+            .deferred_tiles
+            .keys()
+            .map(|&tile_coordinate| (priority(tile_coordinate), tile_coordinate))
+            .collect_vec();
+        deferred_tiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // only loaded tiles and tiles, which have not started loading yet, can be evicted safely
+        let mut evictable_tiles = if self.unused_tiles.len() < deferred_tiles.len() {
+            let mut pending_attachments = HashMap::<TileCoordinate, u32>::default();
+
+            for tile in &self.to_load {
+                *pending_attachments.entry(tile.coordinate).or_default() += 1;
+            }
+
+            self.tile_states
+                .iter()
+                .filter(|(&tile_coordinate, tile)| {
+                    tile.requests > 0
+                        && tile_coordinate.lod > 0
+                        && !pinned_tiles.contains_key(&tile_coordinate)
+                        && match tile.state {
+                            LoadingState::Loaded => true,
+                            LoadingState::Loading(_) => {
+                                pending_attachments.get(&tile_coordinate)
+                                    == Some(&self.attachment_count)
+                            }
+                        }
+                })
+                .map(|(&tile_coordinate, _)| (priority(tile_coordinate), tile_coordinate))
+                .collect_vec()
+        } else {
+            Vec::new()
+        };
+        // the least important tiles are evicted first
+        evictable_tiles.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut evictable_tiles = evictable_tiles.into_iter().peekable();
+
+        let mut evicted_tiles = 0;
+
+        for (deferred_priority, tile_coordinate) in deferred_tiles {
+            if self.unused_tiles.is_empty() {
+                let Some((_, evicted_coordinate)) = evictable_tiles
+                    .next_if(|&(evicted_priority, _)| evicted_priority > deferred_priority)
+                else {
+                    break;
+                };
+
+                self.evict_tile(evicted_coordinate);
+                evicted_tiles += 1;
+            }
+
+            let requests = self.deferred_tiles.remove(&tile_coordinate).unwrap();
+            let atlas_index = self.allocate_tile().unwrap();
+            self.start_loading(tile_coordinate, atlas_index, requests);
+        }
+
+        evicted_tiles
+    }
+
+    /// Frees the atlas index of a requested tile and defers its requests.
+    fn evict_tile(&mut self, tile_coordinate: TileCoordinate) {
+        let tile = self.tile_states.remove(&tile_coordinate).unwrap();
+        self.statistics.pressure_evictions += 1;
+        self.cancel_reloads(tile_coordinate);
+
+        if matches!(tile.state, LoadingState::Loaded) {
+            self.events.push((
+                AtlasTile::new(tile_coordinate, tile.atlas_index),
+                TileEventKind::Evicted,
+            ));
+        }
+
+        self.unused_tiles
+            .push_front(AtlasTile::new(TileCoordinate::INVALID, tile.atlas_index));
+        self.deferred_tiles.insert(tile_coordinate, tile.requests);
+    }
+
+    /// The count of attachments of the tile, which are waiting to be loaded.
+    fn pending_attachments(&self, tile_coordinate: TileCoordinate) -> u32 {
+        self.to_load
+            .iter()
+            .filter(|tile| tile.coordinate == tile_coordinate)
+            .count() as u32
+    }
+
+    fn start_loading(&mut self, tile_coordinate: TileCoordinate, atlas_index: u32, requests: u32) {
+        self.events.push((
+            AtlasTile::new(tile_coordinate, atlas_index),
+            TileEventKind::Requested,
+        ));
+
+        self.tile_states.insert(
+            tile_coordinate,
+            TileState {
+                requests,
+                state: LoadingState::Loading(self.attachment_count),
+                atlas_index,
+            },
+        );
+
+        for attachment_index in 0..self.attachment_count {
+            self.to_load.push_back(AtlasTileAttachment {
+                coordinate: tile_coordinate,
+                atlas_index,
+                attachment_index,
+            });
+        }
+    }
+
+    /// Orders the pending load requests by their priority, so that the most important tiles
+    /// are loaded first. Lower values are loaded first.
+    fn prioritize(&mut self, priority: impl Fn(TileCoordinate) -> f64) {
+        let mut priorities = HashMap::<TileCoordinate, f64>::default();
+
+        for tile in &self.to_load {
+            priorities
+                .entry(tile.coordinate)
+                .or_insert_with(|| priority(tile.coordinate));
+        }
+
+        self.to_load
+            .make_contiguous()
+            .sort_by(|a, b| priorities[&a.coordinate].total_cmp(&priorities[&b.coordinate]));
     }
 
-    fn loaded_tile_attachment(&mut self, tile: AtlasTileAttachment) {
-        self.load_slots += 1;
+    /// The count of atlas indices, which are available for prefetching.
+    /// This is zero, while requests are deferred.
+    pub(crate) fn prefetch_capacity(&self) -> usize {
+        if self.deferred_tiles.is_empty() {
+            self.unused_tiles.len()
+        } else {
+            0
+        }
+    }
+
+    /// Whether the tile is still loading into the atlas index of the attachment.
+    fn is_loading(&self, tile: AtlasTileAttachment) -> bool {
+        self.tile_states
+            .get(&tile.coordinate)
+            .is_some_and(|tile_state| {
+                tile_state.atlas_index == tile.atlas_index
+                    && matches!(tile_state.state, LoadingState::Loading(_))
+            })
+    }
+
+    /// Registers the loaded attachment with its tile.
+    /// Returns false, if the tile is not loading into the atlas index anymore.
+    fn loaded_tile_attachment(&mut self, tile: AtlasTileAttachment) -> bool {
+        self.loading -= 1;
+
+        if self
+            .cancelled_reloads
+            .remove(&(tile.coordinate, tile.attachment_index))
+        {
+            return false;
+        }
+
+        if self
+            .reloading
+            .remove(&(tile.coordinate, tile.attachment_index))
+        {
+            let is_current = self.finish_reload(tile);
+
+            if is_current {
+                self.events.push((
+                    AtlasTile::new(tile.coordinate, tile.atlas_index),
+                    TileEventKind::Reloaded,
+                ));
+            }
+
+            return is_current;
+        }
+
+        if !self.is_loading(tile) {
+            return false;
+        }
+
+        self.load_attempts
+            .remove(&(tile.coordinate, tile.attachment_index));
 
         let tile_state = self.tile_states.get_mut(&tile.coordinate).unwrap();
 
         tile_state.state = match tile_state.state {
-            LoadingState::Loading(1) => LoadingState::Loaded,
+            LoadingState::Loading(1) => {
+                self.events.push((
+                    AtlasTile::new(tile.coordinate, tile.atlas_index),
+                    TileEventKind::Loaded,
+                ));
+                LoadingState::Loaded
+            }
             LoadingState::Loading(n) => LoadingState::Loading(n - 1),
             LoadingState::Loaded => {
                 panic!("Loaded more attachments, than registered with the tile atlas.")
             }
         };
+
+        true
+    }
+
+    /// Reloads the attachment once more, if it has changed during the finished reload.
+    /// Returns whether the tile still occupies the atlas index of the reload.
+    fn finish_reload(&mut self, tile: AtlasTileAttachment) -> bool {
+        let is_current = self
+            .tile_states
+            .get(&tile.coordinate)
+            .is_some_and(|tile_state| tile_state.atlas_index == tile.atlas_index);
+
+        if self.dirty.remove(&(tile.coordinate, tile.attachment_index)) && is_current {
+            self.reloading
+                .insert((tile.coordinate, tile.attachment_index));
+            self.to_load.push_back(tile);
+        }
+
+        is_current
+    }
+
+    /// Cancels the reloads of the tile, which is removed from the atlas.
+    ///
+    /// Reloads, which are still waiting, are dropped, while the ones that have already started
+    /// are discarded once they finish, so that they are not mistaken for the loads of the tile,
+    /// once it is requested again.
+    fn cancel_reloads(&mut self, tile_coordinate: TileCoordinate) {
+        self.dirty
+            .retain(|&(coordinate, _)| coordinate != tile_coordinate);
+
+        let reloads = self
+            .reloading
+            .iter()
+            .filter(|&&(coordinate, _)| coordinate == tile_coordinate)
+            .copied()
+            .collect_vec();
+
+        for (coordinate, attachment_index) in reloads {
+            self.reloading.remove(&(coordinate, attachment_index));
+
+            let waiting = self.to_load.iter().any(|tile| {
+                tile.coordinate == coordinate && tile.attachment_index == attachment_index
+            });
+
+            if !waiting {
+                self.cancelled_reloads.insert((coordinate, attachment_index));
+            }
+        }
+
+        self.to_load
+            .retain(|tile| tile.coordinate != tile_coordinate);
+    }
+
+    /// Schedules the reload of the failed attachment or gives up on the tile,
+    /// once it has failed too often.
+    fn failed_tile_attachment(
+        &mut self,
+        tile: AtlasTileAttachment,
+        attachment: &str,
+        error: anyhow::Error,
+    ) {
+        self.loading -= 1;
+        self.statistics.failed_loads += 1;
+
+        if self
+            .cancelled_reloads
+            .remove(&(tile.coordinate, tile.attachment_index))
+        {
+            return;
+        }
+
+        if self
+            .reloading
+            .remove(&(tile.coordinate, tile.attachment_index))
+        {
+            self.finish_reload(tile);
+
+            // the previous data of the tile stays in use
+            warn!(
+                "Failed to reload the attachment {attachment} of the tile {}: {error:#}",
+                tile.coordinate
+            );
+
+            self.load_errors.push(TileLoadError {
+                terrain: Entity::PLACEHOLDER,
+                coordinate: tile.coordinate,
+                attachment: attachment.to_string(),
+                error: format!("{error:#}"),
+                attempt: 1,
+                permanent: false,
+            });
+
+            return;
+        }
+
+        if !self.is_loading(tile) {
+            return;
+        }
+
+        let attempt = self
+            .load_attempts
+            .entry((tile.coordinate, tile.attachment_index))
+            .or_default();
+        *attempt += 1;
+        let attempt = *attempt;
+
+        let permanent = attempt > self.settings.max_load_retries;
+
+        if permanent {
+            error!(
+                "Failed to load the attachment {attachment} of the tile {}, falling back to its parent: {error:#}",
+                tile.coordinate
+            );
+        } else {
+            warn!(
+                "Failed to load the attachment {attachment} of the tile {} (attempt {attempt}): {error:#}",
+                tile.coordinate
+            );
+        }
+
+        self.load_errors.push(TileLoadError {
+            terrain: Entity::PLACEHOLDER,
+            coordinate: tile.coordinate,
+            attachment: attachment.to_string(),
+            error: format!("{error:#}"),
+            attempt,
+            permanent,
+        });
+
+        if permanent {
+            self.fail_tile(tile.coordinate);
+        } else {
+            let backoff = self.settings.retry_backoff * 2u32.saturating_pow(attempt - 1);
+            self.retries.push((Instant::now() + backoff, tile));
+        }
+    }
+
+    /// Gives up on loading the tile and frees its atlas index.
+    /// Requests of the tile are ignored from now on, so that its parent is used instead.
+    fn fail_tile(&mut self, tile_coordinate: TileCoordinate) {
+        let tile = self.tile_states.remove(&tile_coordinate).unwrap();
+
+        self.cancel_reloads(tile_coordinate);
+        self.retries
+            .retain(|(_, tile)| tile.coordinate != tile_coordinate);
+        self.load_attempts
+            .retain(|&(coordinate, _), _| coordinate != tile_coordinate);
+        self.unused_tiles
+            .retain(|unused_tile| unused_tile.atlas_index != tile.atlas_index);
+        self.unused_tiles
+            .push_front(AtlasTile::new(TileCoordinate::INVALID, tile.atlas_index));
+
+        self.failed_tiles.insert(tile_coordinate, tile.requests);
+        self.events.push((
+            AtlasTile::new(tile_coordinate, tile.atlas_index),
+            TileEventKind::Failed,
+        ));
     }
 
     fn saved_tile_attachment(&mut self, _tile: AtlasTileAttachment) {
-        self.save_slots += 1;
+        self.saving -= 1;
     }
 
     fn downloaded_tile_attachment(&mut self, _tile: AtlasTileAttachment) {
-        self.download_slots += 1;
+        self.downloading -= 1;
     }
 
     fn get_tile(&mut self, tile_coordinate: TileCoordinate) -> AtlasTile {
@@ -380,12 +1062,19 @@ impl TileAtlasState {
         AtlasTile::new(tile_coordinate, atlas_index)
     }
 
-    fn allocate_tile(&mut self) -> u32 {
-        let unused_tile = self.unused_tiles.pop_front().expect("Atlas out of indices");
+    fn allocate_tile(&mut self) -> Option<u32> {
+        let unused_tile = self.unused_tiles.pop_front()?;
 
-        self.tile_states.remove(&unused_tile.coordinate);
+        if let Some(tile) = self.tile_states.remove(&unused_tile.coordinate) {
+            self.statistics.cache_evictions += 1;
+            self.cancel_reloads(unused_tile.coordinate);
 
-        unused_tile.atlas_index
+            if matches!(tile.state, LoadingState::Loaded) {
+                self.events.push((unused_tile, TileEventKind::Evicted));
+            }
+        }
+
+        Some(unused_tile.atlas_index)
     }
 
     fn get_or_allocate_tile(&mut self, tile_coordinate: TileCoordinate) -> AtlasTile {
@@ -398,7 +1087,7 @@ impl TileAtlasState {
         let atlas_index = if let Some(tile) = self.tile_states.get(&tile_coordinate) {
             tile.atlas_index
         } else {
-            let atlas_index = self.allocate_tile();
+            let atlas_index = self.allocate_tile().expect("Atlas out of indices");
 
             self.tile_states.insert(
                 tile_coordinate,
@@ -415,15 +1104,19 @@ impl TileAtlasState {
         AtlasTile::new(tile_coordinate, atlas_index)
     }
 
-    fn request_tile(&mut self, tile_coordinate: TileCoordinate) {
+    pub(super) fn request_tile(&mut self, tile_coordinate: TileCoordinate) {
         if !self.existing_tiles.contains(&tile_coordinate) {
             return;
         }
 
-        let mut tile_states = mem::take(&mut self.tile_states);
+        if let Some(requests) = self.failed_tiles.get_mut(&tile_coordinate) {
+            // the requests are kept, in case the data of the tile changes
+            *requests += 1;
+            return;
+        }
 
         // check if the tile is already present else start loading it
-        if let Some(tile) = tile_states.get_mut(&tile_coordinate) {
+        if let Some(tile) = self.tile_states.get_mut(&tile_coordinate) {
             if tile.requests == 0 {
                 // the tile is now used again
                 self.unused_tiles
@@ -431,36 +1124,44 @@ impl TileAtlasState {
             }
 
             tile.requests += 1;
+        } else if let Some(requests) = self.deferred_tiles.get_mut(&tile_coordinate) {
+            *requests += 1;
         } else {
-            // Todo: implement better loading strategy
-            let atlas_index = self.allocate_tile();
-
-            tile_states.insert(
-                tile_coordinate,
-                TileState {
-                    requests: 1,
-                    state: LoadingState::Loading(self.attachment_count),
-                    atlas_index,
-                },
-            );
-
-            for attachment_index in 0..self.attachment_count {
-                self.to_load.push_back(AtlasTileAttachment {
-                    coordinate: tile_coordinate,
-                    atlas_index,
-                    attachment_index,
-                });
-            }
+            self.load_or_defer(tile_coordinate, 1);
         }
+    }
 
-        self.tile_states = tile_states;
+    fn load_or_defer(&mut self, tile_coordinate: TileCoordinate, requests: u32) {
+        if let Some(atlas_index) = self.allocate_tile() {
+            self.start_loading(tile_coordinate, atlas_index, requests);
+        } else {
+            // the atlas is full, so the tile is loaded once an index becomes available
+            self.deferred_tiles.insert(tile_coordinate, requests);
+        }
     }
 
-    fn release_tile(&mut self, tile_coordinate: TileCoordinate) {
+    pub(super) fn release_tile(&mut self, tile_coordinate: TileCoordinate) {
         if !self.existing_tiles.contains(&tile_coordinate) {
             return;
         }
 
+        if let Some(requests) = self.failed_tiles.get_mut(&tile_coordinate) {
+            *requests -= 1;
+            return;
+        }
+
+        if let Some(requests) = self.deferred_tiles.get_mut(&tile_coordinate) {
+            *requests -= 1;
+
+            if *requests == 0 {
+                self.deferred_tiles.remove(&tile_coordinate);
+            }
+
+            return;
+        }
+
+        let pending = self.pending_attachments(tile_coordinate);
+
         let tile = self
             .tile_states
             .get_mut(&tile_coordinate)
@@ -468,10 +1169,149 @@ impl TileAtlasState {
         tile.requests -= 1;
 
         if tile.requests == 0 {
-            // the tile is not used anymore
-            self.unused_tiles
-                .push_back(AtlasTile::new(tile_coordinate, tile.atlas_index));
+            if matches!(tile.state, LoadingState::Loading(_)) && pending == self.attachment_count {
+                // none of the attachments has started loading yet, so the request is cancelled
+                // and the atlas index is reused first
+                self.to_load
+                    .retain(|tile| tile.coordinate != tile_coordinate);
+                self.unused_tiles
+                    .push_front(AtlasTile::new(TileCoordinate::INVALID, tile.atlas_index));
+                self.tile_states.remove(&tile_coordinate);
+            } else {
+                // the tile is not used anymore
+                self.unused_tiles
+                    .push_back(AtlasTile::new(tile_coordinate, tile.atlas_index));
+            }
+        }
+    }
+
+    /// Frees the atlas indices outside of the atlas size.
+    /// The tiles, which occupy them, are loaded again once an index becomes available.
+    fn clamp_atlas_size(&mut self, atlas_size: u32) {
+        self.unused_tiles
+            .retain(|tile| tile.atlas_index < atlas_size);
+
+        let clamped_tiles = self
+            .tile_states
+            .iter()
+            .filter(|(_, tile)| tile.atlas_index >= atlas_size)
+            .map(|(&tile_coordinate, _)| tile_coordinate)
+            .collect_vec();
+
+        for tile_coordinate in clamped_tiles {
+            let tile = self.tile_states.remove(&tile_coordinate).unwrap();
+            self.cancel_reloads(tile_coordinate);
+
+            if tile.requests > 0 {
+                self.deferred_tiles.insert(tile_coordinate, tile.requests);
+            }
+        }
+
+        self.to_load.retain(|tile| tile.atlas_index < atlas_size);
+    }
+
+    /// Reloads the attachment of the tile, if it is loaded.
+    ///
+    /// Tiles, which are not loaded, will load the changed data once they are requested.
+    /// Failed tiles are loaded again, if they are still requested, since their changed data
+    /// might load successfully.
+    fn reload_tile(&mut self, tile_coordinate: TileCoordinate, attachment_index: u32) {
+        if let Some(requests) = self.failed_tiles.remove(&tile_coordinate) {
+            if requests > 0 {
+                self.load_or_defer(tile_coordinate, requests);
+            }
+
+            return;
+        }
+
+        let Some(tile) = self.tile_states.get(&tile_coordinate) else {
+            return;
+        };
+
+        if !matches!(tile.state, LoadingState::Loaded) {
+            return;
         }
+
+        if !self.reloading.insert((tile_coordinate, attachment_index)) {
+            // the running reload might have read the previous data
+            self.dirty.insert((tile_coordinate, attachment_index));
+            return;
+        }
+
+        self.to_load.push_back(AtlasTileAttachment {
+            coordinate: tile_coordinate,
+            atlas_index: tile.atlas_index,
+            attachment_index,
+        });
+    }
+
+    fn pin_tiles(&mut self, tiles: impl IntoIterator<Item = TileCoordinate>) -> TilePin {
+        let tiles = tiles
+            .into_iter()
+            .filter(|tile| self.existing_tiles.contains(tile))
+            .unique()
+            .collect_vec();
+
+        for &tile in &tiles {
+            *self.pinned_tiles.entry(tile).or_default() += 1;
+            self.request_tile(tile);
+        }
+
+        let pin = self.next_pin;
+        self.next_pin += 1;
+        self.pins.insert(pin, tiles);
+
+        TilePin(pin)
+    }
+
+    fn unpin(&mut self, pin: TilePin) {
+        let Some(tiles) = self.pins.remove(&pin.0) else {
+            return;
+        };
+
+        for tile in tiles {
+            let pins = self.pinned_tiles.get_mut(&tile).unwrap();
+            *pins -= 1;
+
+            if *pins == 0 {
+                self.pinned_tiles.remove(&tile);
+            }
+
+            self.release_tile(tile);
+        }
+    }
+
+    fn statistics(&self, attachments: &[AtlasAttachment]) -> TileAtlasStatistics {
+        let mut statistics = self.statistics.clone();
+
+        for tile in self.tile_states.values() {
+            match tile.state {
+                LoadingState::Loading(_) => statistics.loading_tiles += 1,
+                LoadingState::Loaded if tile.requests > 0 => statistics.loaded_tiles += 1,
+                LoadingState::Loaded => statistics.cached_tiles += 1,
+            }
+        }
+
+        statistics.free_tiles = self
+            .unused_tiles
+            .iter()
+            .filter(|tile| !self.tile_states.contains_key(&tile.coordinate))
+            .count() as u32;
+        statistics.pending_loads = self.to_load.len() as u32;
+        statistics.deferred_tiles = self.deferred_tiles.len() as u32;
+        statistics.attachment_bytes = attachments
+            .iter()
+            .map(|attachment| {
+                attachment
+                    .data
+                    .iter()
+                    .filter(|data| !matches!(data, AttachmentData::None))
+                    .map(|data| data.bytes().len() as u64)
+                    .sum()
+            })
+            .collect();
+
+        statistics
     }
 
     fn get_best_tile(&self, tile_coordinate: TileCoordinate) -> TileTreeEntry {
@@ -524,29 +1364,54 @@ pub struct TileAtlas {
     pub(crate) atlas_size: u32,
     pub(crate) lod_count: u32,
     pub(crate) model: TerrainModel,
+    pub(crate) source: Arc<dyn TileSource>,
+    /// Whether the tiles of the atlas are preprocessed.
+    pub(crate) preprocessing: bool,
 }
 
 impl TileAtlas {
     /// Creates a new tile_tree from a terrain config.
     pub fn new(config: &TerrainConfig) -> Self {
+        let atlas_size = config.effective_atlas_size();
+
+        let source = config
+            .tile_source
+            .clone()
+            .unwrap_or_else(|| config.tile_layout.tile_source(&config.path));
+
         let attachments = config
             .attachments
             .iter()
-            .map(|attachment| AtlasAttachment::new(attachment, config.atlas_size, &config.path))
+            .enumerate()
+            .map(|(index, attachment)| {
+                AtlasAttachment::new(
+                    attachment,
+                    index == 0,
+                    atlas_size,
+                    &config.path,
+                    source.clone(),
+                )
+            })
             .collect_vec();
 
-        let existing_tiles = Self::load_tile_config(&config.path);
+        let existing_tiles = Self::load_tile_config(config);
 
-        let state =
-            TileAtlasState::new(config.atlas_size, attachments.len() as u32, existing_tiles);
+        let state = TileAtlasState::new(
+            atlas_size,
+            attachments.len() as u32,
+            existing_tiles,
+            config.streaming,
+        );
 
         Self {
             model: config.model.clone(),
             attachments,
             state,
             path: config.path.to_string(),
-            atlas_size: config.atlas_size,
+            atlas_size,
             lod_count: config.lod_count,
+            source,
+            preprocessing: false,
         }
     }
 
@@ -558,6 +1423,116 @@ impl TileAtlas {
         self.state.get_or_allocate_tile(tile_coordinate)
     }
 
+    /// Keeps the tiles loaded, independent of any [`TileTree`].
+    ///
+    /// Pinned tiles are loaded before all other tiles and are never evicted,
+    /// even if the atlas runs out of indices.
+    /// Tiles, which do not exist in the terrain, are ignored.
+    pub fn pin_tiles(&mut self, tiles: impl IntoIterator<Item = TileCoordinate>) -> TilePin {
+        self.state.pin_tiles(tiles)
+    }
+
+    /// Pins all tiles of the LOD range, which are closer to the world position than the radius.
+    ///
+    /// The position is relative to the reference frame of the terrain, like the positions of the views.
+    pub fn pin_region(&mut self, center: DVec3, radius: f64, lods: Range<u32>) -> TilePin {
+        let model = &self.model;
+        let height = (model.min_height + model.max_height) / 2.0;
+        let coordinate = Coordinate::from_world_position(center, model);
+
+        let mut tiles = Vec::new();
+
+        for side in 0..model.side_count() {
+            let coordinate = coordinate.project_to_side(side, model);
+
+            for lod in lods.start..lods.end.min(self.lod_count) {
+                let tile_count = TileCoordinate::count(lod) as f64;
+                // a conservative estimate of the tiles covered by the radius
+                let extent = radius / model.scale() * tile_count;
+                let min = ((coordinate.uv * tile_count) - extent)
+                    .floor()
+                    .max(DVec2::ZERO)
+                    .as_uvec2();
+                let max = ((coordinate.uv * tile_count) + extent)
+                    .ceil()
+                    .min(DVec2::splat(tile_count))
+                    .as_uvec2();
+
+                for (x, y) in iproduct!(min.x..max.x, min.y..max.y) {
+                    // the closest point of the tile to the center
+                    let uv = (coordinate.uv * tile_count).clamp(
+                        DVec2::new(x as f64, y as f64),
+                        DVec2::new(x as f64 + 1.0, y as f64 + 1.0),
+                    ) / tile_count;
+
+                    if Coordinate::new(side, uv)
+                        .world_position(model, height)
+                        .distance(center)
+                        < radius
+                    {
+                        tiles.push(TileCoordinate::new(side, lod, x, y));
+                    }
+                }
+            }
+        }
+
+        self.pin_tiles(tiles)
+    }
+
+    /// Releases the tiles of the pin, which returns them to the least recently used cache,
+    /// unless they are pinned or requested otherwise.
+    pub fn unpin(&mut self, pin: TilePin) {
+        self.state.unpin(pin);
+    }
+
+    /// The amount of tiles the tile atlas can hold.
+    pub fn atlas_size(&self) -> u32 {
+        self.atlas_size
+    }
+
+    /// The offset and scale, which convert a sample of the height attachment into meters.
+    pub(crate) fn height_transform(&self) -> (f32, f32) {
+        self.attachments[0]
+            .format
+            .height_transform(self.model.min_height, self.model.max_height)
+    }
+
+    /// The block compression of the attachment inside the GPU atlas.
+    ///
+    /// Tiles are preprocessed uncompressed, their blocks are encoded once they are saved.
+    pub(crate) fn gpu_block_compression(&self, attachment: &AtlasAttachment) -> BlockCompression {
+        if self.preprocessing {
+            BlockCompression::None
+        } else {
+            attachment.block_compression
+        }
+    }
+
+    /// The memory the attachments of the tile atlas occupy.
+    pub fn memory_footprint(&self) -> Vec<AttachmentFootprint> {
+        self.attachments
+            .iter()
+            .map(|attachment| AttachmentFootprint::new(&attachment.config(), self.atlas_size))
+            .collect()
+    }
+
+    /// The current streaming statistics of the tile atlas.
+    pub fn statistics(&self) -> TileAtlasStatistics {
+        self.state.statistics(&self.attachments)
+    }
+
+    /// The settings, which limit how many tiles are streamed concurrently.
+    pub fn streaming_settings(&self) -> StreamingSettings {
+        self.state.settings
+    }
+
+    /// Adjusts the streaming limits at runtime.
+    ///
+    /// Tiles, which are already streaming, are not affected by lowered limits.
+    pub fn set_streaming_settings(&mut self, settings: StreamingSettings) {
+        self.state.settings = settings;
+    }
+
     pub fn save(&mut self, tile: AtlasTileAttachment) {
         self.state.to_save.push_back(tile);
     }
@@ -573,22 +1548,23 @@ impl TileAtlas {
     /// Updates the tile atlas according to all corresponding tile_trees.
     pub(crate) fn update(
         mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
-        mut tile_atlases: Query<&mut TileAtlas>,
+        mut tile_atlases: Query<(Entity, &mut TileAtlas, Option<&mut TileAtlasStatistics>)>,
+        mut exhausted_events: EventWriter<TileAtlasExhausted>,
+        mut tile_events: EventWriter<TileEvent>,
+        mut load_errors: EventWriter<TileLoadError>,
     ) {
-        for mut tile_atlas in tile_atlases.iter_mut() {
+        for (_, mut tile_atlas, _) in tile_atlases.iter_mut() {
             let TileAtlas {
                 state, attachments, ..
             } = tile_atlas.deref_mut();
 
-            state.update(attachments);
-
             for attachment in attachments {
                 attachment.update(state);
             }
         }
 
         for (&(terrain, _view), tile_tree) in tile_trees.iter_mut() {
-            let mut tile_atlas = tile_atlases.get_mut(terrain).unwrap();
+            let (_, mut tile_atlas, _) = tile_atlases.get_mut(terrain).unwrap();
 
             for tile_coordinate in tile_tree.released_tiles.drain(..) {
                 tile_atlas.state.release_tile(tile_coordinate);
@@ -598,27 +1574,270 @@ impl TileAtlas {
                 tile_atlas.state.request_tile(tile_coordinate);
             }
         }
+
+        for (terrain, mut tile_atlas, statistics) in tile_atlases.iter_mut() {
+            let TileAtlas {
+                state,
+                attachments,
+                model,
+                source,
+                ..
+            } = tile_atlas.deref_mut();
+
+            for (attachment, tile_coordinate) in source.changed_tiles() {
+                if let Some(attachment_index) = attachments
+                    .iter()
+                    .position(|atlas_attachment| atlas_attachment.name == attachment)
+                {
+                    state.reload_tile(tile_coordinate, attachment_index as u32);
+                }
+            }
+
+            // the priorities are re-evaluated every frame, since the views move
+            let views = tile_trees
+                .iter()
+                .filter(|(&(view_terrain, _), _)| view_terrain == terrain)
+                .map(|(_, tile_tree)| tile_tree)
+                .collect_vec();
+
+            let evicted_tiles = state.update(attachments, |tile| {
+                views
+                    .iter()
+                    .map(|tile_tree| tile_tree.load_priority(tile, model))
+                    .fold(f64::INFINITY, f64::min)
+            });
+
+            if !state.deferred_tiles.is_empty() {
+                exhausted_events.send(TileAtlasExhausted {
+                    terrain,
+                    deferred_tiles: state.deferred_tiles.len() as u32,
+                    evicted_tiles,
+                });
+            }
+
+            tile_events.send_batch(state.events.drain(..).map(|(tile, kind)| TileEvent {
+                terrain,
+                coordinate: tile.coordinate,
+                atlas_index: tile.atlas_index,
+                kind,
+            }));
+
+            load_errors.send_batch(
+                state
+                    .load_errors
+                    .drain(..)
+                    .map(|error| TileLoadError { terrain, ..error }),
+            );
+
+            if let Some(mut statistics) = statistics {
+                *statistics = state.statistics(attachments);
+            }
+        }
     }
 
-    /// Saves the tile configuration of the terrain, which stores the [`TileCoordinate`]s of all the tiles
-    /// of the terrain.
-    pub(crate) fn save_tile_config(&self) {
-        let tc = TC {
-            tiles: self.state.existing_tiles.iter().copied().collect_vec(),
+    /// Checks the attachments of newly spawned terrains against the features of the render device.
+    ///
+    /// Block compressed attachments fall back to uncompressed tiles, if the device does not
+    /// support block compression. Terrains with attachment formats the device can not sample
+    /// are not rendered.
+    pub(crate) fn check_device_features(
+        mut commands: Commands,
+        device: Option<Res<RenderDevice>>,
+        mut tile_atlases: Query<(Entity, &mut TileAtlas), Added<TileAtlas>>,
+    ) {
+        let Some(device) = device else {
+            return;
         };
 
-        tc.save_file(format!("assets/{}/config.tc", &self.path))
+        let features = device.features();
+
+        for (terrain, mut tile_atlas) in &mut tile_atlases {
+            let TileAtlas {
+                attachments,
+                path,
+                preprocessing,
+                ..
+            } = tile_atlas.deref_mut();
+
+            for attachment in attachments {
+                let name = &attachment.name;
+
+                let required_features = attachment.format.required_features();
+
+                if !features.contains(required_features) {
+                    error!(
+                        "The {:?} format of the attachment {name} of the terrain {path} requires the {required_features:?} features of the render device, so the terrain is not rendered.",
+                        attachment.format
+                    );
+
+                    commands.entity(terrain).remove::<TileAtlas>();
+                    break;
+                }
+
+                let required_features = attachment.block_compression.required_features();
+
+                // the blocks are encoded on the CPU, so they are still stored while preprocessing
+                if !*preprocessing && !features.contains(required_features) {
+                    error!(
+                        "The {:?} compression of the attachment {name} of the terrain {path} requires the {required_features:?} features of the render device, falling back to uncompressed tiles.",
+                        attachment.block_compression
+                    );
+
+                    attachment.block_compression = BlockCompression::None;
+                }
+            }
+        }
+    }
+
+    /// Limits the atlas size of newly spawned terrains to the maximum amount of
+    /// texture array layers supported by the render device.
+    pub(crate) fn clamp_atlas_size(
+        device: Option<Res<RenderDevice>>,
+        mut tile_atlases: Query<&mut TileAtlas, Added<TileAtlas>>,
+    ) {
+        let Some(device) = device else {
+            return;
+        };
+
+        let max_atlas_size = device.limits().max_texture_array_layers;
+
+        for mut tile_atlas in &mut tile_atlases {
+            let TileAtlas {
+                attachments,
+                state,
+                path,
+                atlas_size,
+                ..
+            } = tile_atlas.deref_mut();
+
+            if *atlas_size <= max_atlas_size {
+                continue;
+            }
+
+            warn!(
+                "The atlas size {atlas_size} of the terrain {path} exceeds the {max_atlas_size} texture array layers supported by the render device and is clamped."
+            );
+
+            *atlas_size = max_atlas_size;
+
+            for attachment in attachments {
+                attachment.data.truncate(max_atlas_size as usize);
+            }
+
+            state.clamp_atlas_size(max_atlas_size);
+        }
+    }
+
+    /// Discards the loaded tiles, which would otherwise be uploaded to the GPU,
+    /// when running without a renderer.
+    pub(crate) fn discard_uploads(mut tile_atlases: Query<&mut TileAtlas>) {
+        for mut tile_atlas in tile_atlases.iter_mut() {
+            for attachment in &mut tile_atlas.attachments {
+                attachment.uploading_tiles.clear();
+            }
+        }
+    }
+
+    /// Saves the manifest of the terrain, which describes the model, the attachments and
+    /// the [`TileCoordinate`]s of all the tiles of the terrain.
+    pub(crate) fn save_tile_config(&self) {
+        let manifest = TerrainManifest::new(
+            self.lod_count,
+            &self.model,
+            self.attachments
+                .iter()
+                .map(AtlasAttachment::config)
+                .collect(),
+            self.state.existing_tiles.iter().copied().collect(),
+        );
+
+        manifest
+            .save_file(TerrainManifest::path(&self.path))
             .unwrap();
     }
 
-    /// Loads the tile configuration of the terrain, which stores the [`TileCoordinate`]s of all the tiles
-    /// of the terrain.
-    pub(crate) fn load_tile_config(path: &str) -> HashSet<TileCoordinate> {
-        if let Ok(tc) = TC::load_file(format!("assets/{}/config.tc", path)) {
-            tc.tiles.into_iter().collect()
-        } else {
-            println!("Tile config not found.");
-            HashSet::default()
+    /// Loads the [`TileCoordinate`]s of all the tiles of the terrain from its manifest.
+    ///
+    /// If the manifest does not match the config, the error is reported and the terrain is
+    /// treated as empty, instead of interpreting the tiles incorrectly.
+    pub(crate) fn load_tile_config(config: &TerrainConfig) -> HashSet<TileCoordinate> {
+        let path = TerrainManifest::path(&config.path);
+
+        if !Path::new(&path).exists() {
+            println!("Terrain manifest not found.");
+            return HashSet::default();
+        }
+
+        let manifest = TerrainManifest::load_file(&path).and_then(|manifest| {
+            manifest.validate(config)?;
+            Ok(manifest)
+        });
+
+        match manifest {
+            Ok(manifest) => manifest.tiles.into_iter().collect(),
+            Err(error) => {
+                error!("Failed to load the terrain manifest {path}: {error}");
+                HashSet::default()
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_next_load(state: &mut TileAtlasState) -> AtlasTileAttachment {
+        state.loading += 1;
+        state.to_load.pop_front().unwrap()
+    }
+
+    fn is_loaded(state: &TileAtlasState, tile_coordinate: TileCoordinate) -> bool {
+        state.get_best_tile(tile_coordinate).atlas_lod == tile_coordinate.lod
+    }
+
+    #[test]
+    fn evicted_reloads_are_cancelled() {
+        let tile = TileCoordinate::new(0, 1, 0, 0);
+        let other = TileCoordinate::new(0, 1, 1, 0);
+
+        let mut state = TileAtlasState::new(
+            1,
+            1,
+            HashSet::from_iter([tile, other]),
+            default(),
+        );
+
+        state.request_tile(tile);
+        let load = start_next_load(&mut state);
+        assert!(state.loaded_tile_attachment(load));
+        assert!(is_loaded(&state, tile));
+
+        // the waiting reload is dropped, once the tile is evicted
+        state.reload_tile(tile, 0);
+        state.evict_tile(tile);
+        state.allocate_deferred_tiles(&|_| 0.0, &default());
+
+        let load = start_next_load(&mut state);
+        assert!(state.loaded_tile_attachment(load));
+        assert!(is_loaded(&state, tile));
+
+        // the running reload is discarded, once the tile is evicted and requested again
+        state.reload_tile(tile, 0);
+        let reload = start_next_load(&mut state);
+        state.release_tile(tile);
+        state.request_tile(other);
+        state.release_tile(other);
+        state.request_tile(tile);
+
+        let load = start_next_load(&mut state);
+        assert!(!state.loaded_tile_attachment(reload));
+        assert!(!is_loaded(&state, tile));
+        assert!(state.loaded_tile_attachment(load));
+        assert!(is_loaded(&state, tile));
+
+        assert!(state.reloading.is_empty());
+        assert!(state.cancelled_reloads.is_empty());
+        assert_eq!(state.loading, 0);
+    }
+}
@@ -0,0 +1,499 @@
+use crate::math::TileCoordinate;
+use anyhow::{bail, Context, Result};
+use bevy::{
+    prelude::*,
+    utils::{hashbrown::hash_map::Entry, BoxedFuture, HashMap},
+};
+use bincode::{config, Decode, Encode};
+use memmap2::Mmap;
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    ops::Range,
+    sync::{Arc, Mutex, RwLock},
+};
+
+#[cfg(feature = "file_watcher")]
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// A storage backend, from which the [`TileAtlas`](super::tile_atlas::TileAtlas) loads
+/// and into which it saves the encoded attachment data of its tiles.
+///
+/// Each tile attachment is identified by the name of the attachment, the [`TileCoordinate`]
+/// of the tile and the extension of its encoding (e.g. `bin` or `png`).
+/// The source only transfers the encoded bytes, decoding and encoding is handled by the atlas.
+///
+/// Implement this trait to serve tiles from archives, procedural generators, caches, etc.
+pub trait TileSource: Send + Sync + 'static {
+    /// Loads the encoded data of the attachment of a tile.
+    fn load_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>>>;
+
+    /// Saves the encoded data of the attachment of a tile.
+    fn save_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+        data: &'a [u8],
+    ) -> BoxedFuture<'a, Result<()>>;
+
+    /// Returns the attachments of the tiles (attachment name and [`TileCoordinate`]),
+    /// which have changed since the last call.
+    ///
+    /// The tile atlas polls this every frame and reloads the affected tiles, which are loaded.
+    /// Sources, which do not support change notifications, return nothing.
+    fn changed_tiles(&self) -> Vec<(String, TileCoordinate)> {
+        Vec::new()
+    }
+}
+
+/// The default [`TileSource`], which stores each tile attachment in its own file
+/// at `assets/{path}/data/{attachment}/{tile_coordinate}.{extension}`.
+pub struct FileTileSource {
+    path: String,
+    #[cfg(feature = "file_watcher")]
+    watcher: Option<(notify::RecommendedWatcher, Arc<Mutex<HashSet<PathBuf>>>)>,
+}
+
+impl FileTileSource {
+    /// Creates a new file tile source for the terrain folder inside the assets directory.
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: format!("assets/{path}/data"),
+            #[cfg(feature = "file_watcher")]
+            watcher: None,
+        }
+    }
+
+    /// Creates a new file tile source, which watches the terrain folder for changes,
+    /// so that tiles are reloaded, once they are rewritten (e.g. by the preprocessor).
+    #[cfg(feature = "file_watcher")]
+    pub fn watched(path: &str) -> Result<Self> {
+        use notify::{RecursiveMode, Watcher};
+
+        let mut source = Self::new(path);
+        let changed_files = Arc::new(Mutex::new(HashSet::new()));
+
+        let mut watcher = notify::recommended_watcher({
+            let changed_files = changed_files.clone();
+
+            move |event: notify::Result<notify::Event>| {
+                if let Ok(event) = event {
+                    if event.kind.is_create() || event.kind.is_modify() {
+                        changed_files.lock().unwrap().extend(event.paths);
+                    }
+                }
+            }
+        })?;
+
+        fs::create_dir_all(&source.path)?;
+        watcher.watch(Path::new(&source.path), RecursiveMode::Recursive)?;
+
+        source.watcher = Some((watcher, changed_files));
+
+        Ok(source)
+    }
+
+    fn tile_path(&self, attachment: &str, coordinate: TileCoordinate, extension: &str) -> String {
+        coordinate.path(&format!("{}/{attachment}", self.path), extension)
+    }
+}
+
+impl TileSource for FileTileSource {
+    fn load_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let path = self.tile_path(attachment, coordinate, extension);
+
+            fs::read(&path).with_context(|| format!("Failed to read the tile {path}"))
+        })
+    }
+
+    #[cfg(feature = "file_watcher")]
+    fn changed_tiles(&self) -> Vec<(String, TileCoordinate)> {
+        let Some((_, changed_files)) = &self.watcher else {
+            return Vec::new();
+        };
+
+        // the files are located at {attachment}/{tile_coordinate}.{extension}
+        changed_files
+            .lock()
+            .unwrap()
+            .drain()
+            .filter_map(|path| {
+                let coordinate = TileCoordinate::parse(path.file_stem()?.to_str()?)?;
+                let attachment = path.parent()?.file_name()?.to_str()?.to_string();
+
+                Some((attachment, coordinate))
+            })
+            .collect()
+    }
+
+    fn save_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+        data: &'a [u8],
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let path = self.tile_path(attachment, coordinate, extension);
+
+            Ok(fs::write(path, data)?)
+        })
+    }
+}
+
+/// The on-disk layout of the tiles of a terrain, used to create the default [`TileSource`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TileLayout {
+    /// Each tile attachment is stored in its own file. See [`FileTileSource`].
+    #[default]
+    Directory,
+    /// The tiles of each attachment are packed into a single archive. See [`ArchiveTileSource`].
+    Archive,
+}
+
+impl TileLayout {
+    /// Creates the tile source for the terrain folder inside the assets directory.
+    pub fn tile_source(self, path: &str) -> Arc<dyn TileSource> {
+        match self {
+            TileLayout::Directory => Arc::new(FileTileSource::new(path)),
+            TileLayout::Archive => Arc::new(ArchiveTileSource::new(path)),
+        }
+    }
+}
+
+const ARCHIVE_MAGIC: [u8; 4] = *b"BTTA";
+const ARCHIVE_VERSION: u32 = 1;
+const ARCHIVE_HEADER_SIZE: usize = 8;
+
+/// Identifies a tile attachment inside a [`TileArchive`].
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Hash)]
+struct ArchiveKey {
+    coordinate: TileCoordinate,
+    extension: String,
+}
+
+/// A single, appendable file storing all tiles of an attachment.
+///
+/// The archive starts with a header (magic and version), followed by a sequence of records.
+/// Each record consists of the length and encoding of its [`ArchiveKey`],
+/// the length of its data and the data itself.
+/// Saving a tile appends a new record, which supersedes all previous records of the same tile.
+/// The index of all records is rebuilt when the archive is opened.
+/// A partial record at the end of the archive (e.g. after a crash during a save) is truncated.
+struct TileArchive {
+    path: String,
+    file: File,
+    mmap: Option<Arc<Mmap>>,
+    index: HashMap<ArchiveKey, Range<usize>>,
+    len: usize,
+}
+
+impl TileArchive {
+    fn open(path: String) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(&ARCHIVE_MAGIC)?;
+            file.write_all(&ARCHIVE_VERSION.to_le_bytes())?;
+        }
+
+        let mut archive = Self {
+            path,
+            file,
+            mmap: None,
+            index: default(),
+            len: 0,
+        };
+
+        archive.read_index()?;
+
+        Ok(archive)
+    }
+
+    fn map(&mut self) -> Result<Arc<Mmap>> {
+        let is_outdated = match &self.mmap {
+            Some(mmap) => mmap.len() < self.len,
+            None => true,
+        };
+
+        if is_outdated {
+            // Safety: the archive is only ever appended to, so the mapped bytes are never modified.
+            self.mmap = Some(Arc::new(unsafe { Mmap::map(&self.file)? }));
+        }
+
+        Ok(self.mmap.clone().unwrap())
+    }
+
+    fn read_index(&mut self) -> Result<()> {
+        self.len = self.file.metadata()?.len() as usize;
+
+        let path = self.path.clone();
+        let bytes = self.map()?;
+
+        if bytes.len() < ARCHIVE_HEADER_SIZE || bytes[0..4] != ARCHIVE_MAGIC {
+            bail!("The file {path} is not a tile archive.");
+        }
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into()?);
+
+        if version != ARCHIVE_VERSION {
+            bail!("The tile archive {path} has version {version}, but version {ARCHIVE_VERSION} is required.");
+        }
+
+        let mut index = HashMap::default();
+        let mut offset = ARCHIVE_HEADER_SIZE;
+
+        while offset < bytes.len() {
+            match Self::read_record(&bytes, offset) {
+                Some((key, range)) => {
+                    offset = range.end;
+                    index.insert(key, range);
+                }
+                None => break,
+            }
+        }
+
+        if offset < bytes.len() {
+            warn!("The last record of the tile archive {path} is incomplete and has been removed.");
+
+            // unmap the archive before truncating it
+            drop(bytes);
+            self.mmap = None;
+            self.file.set_len(offset as u64)?;
+            self.len = offset;
+        }
+
+        self.index = index;
+
+        Ok(())
+    }
+
+    /// Reads the key and the data range of the record starting at the offset.
+    /// Returns `None`, if the record is incomplete.
+    fn read_record(bytes: &[u8], offset: usize) -> Option<(ArchiveKey, Range<usize>)> {
+        let mut offset = offset;
+
+        let mut take = |size: usize| {
+            let slice = bytes.get(offset..offset.checked_add(size)?);
+            offset += size;
+            slice
+        };
+
+        let key_size = u32::from_le_bytes(take(4)?.try_into().ok()?) as usize;
+        let (key, _) = bincode::decode_from_slice(take(key_size)?, config::standard()).ok()?;
+        let data_size = u64::from_le_bytes(take(8)?.try_into().ok()?) as usize;
+        take(data_size)?;
+
+        Some((key, offset - data_size..offset))
+    }
+
+    /// Returns the mapped archive and the range of the data of the tile,
+    /// so that the data can be copied without holding the lock of the archive.
+    fn read(&mut self, key: &ArchiveKey) -> Result<(Arc<Mmap>, Range<usize>)> {
+        let Some(range) = self.index.get(key).cloned() else {
+            bail!(
+                "The tile {} is missing in the archive {}.",
+                key.coordinate,
+                self.path
+            );
+        };
+
+        Ok((self.map()?, range))
+    }
+
+    fn write(&mut self, key: ArchiveKey, data: &[u8]) -> Result<()> {
+        let encoded_key = bincode::encode_to_vec(&key, config::standard())?;
+
+        let mut record = Vec::with_capacity(encoded_key.len() + data.len() + 12);
+        record.extend_from_slice(&(encoded_key.len() as u32).to_le_bytes());
+        record.extend_from_slice(&encoded_key);
+        record.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        record.extend_from_slice(data);
+
+        self.file.write_all(&record)?;
+
+        let start = self.len + record.len() - data.len();
+        self.len += record.len();
+        self.index.insert(key, start..self.len);
+
+        Ok(())
+    }
+}
+
+/// A [`TileSource`], which packs all tiles of each attachment into a single archive
+/// at `assets/{path}/data/{attachment}.archive`.
+///
+/// The archives are memory-mapped, so that the tiles can be read with random access.
+pub struct ArchiveTileSource {
+    path: String,
+    archives: RwLock<HashMap<String, Arc<Mutex<TileArchive>>>>,
+}
+
+impl ArchiveTileSource {
+    /// Creates a new archive tile source for the terrain folder inside the assets directory.
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: format!("assets/{path}/data"),
+            archives: default(),
+        }
+    }
+
+    /// The path of the archive storing the tiles of the attachment.
+    pub fn archive_path(directory: &str) -> String {
+        format!("{directory}.archive")
+    }
+
+    fn archive(&self, attachment: &str) -> Result<Arc<Mutex<TileArchive>>> {
+        if let Some(archive) = self.archives.read().unwrap().get(attachment) {
+            return Ok(archive.clone());
+        }
+
+        let mut archives = self.archives.write().unwrap();
+
+        let archive = match archives.entry(attachment.to_string()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = Self::archive_path(&format!("{}/{attachment}", self.path));
+                entry.insert(Arc::new(Mutex::new(TileArchive::open(path)?)))
+            }
+        };
+
+        Ok(archive.clone())
+    }
+}
+
+impl TileSource for ArchiveTileSource {
+    fn load_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+    ) -> BoxedFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            let key = ArchiveKey {
+                coordinate,
+                extension: extension.to_string(),
+            };
+
+            let (mmap, range) = self.archive(attachment)?.lock().unwrap().read(&key)?;
+
+            Ok(mmap[range].to_vec())
+        })
+    }
+
+    fn save_tile<'a>(
+        &'a self,
+        attachment: &'a str,
+        coordinate: TileCoordinate,
+        extension: &'a str,
+        data: &'a [u8],
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let key = ArchiveKey {
+                coordinate,
+                extension: extension.to_string(),
+            };
+
+            self.archive(attachment)?.lock().unwrap().write(key, data)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(x: u32) -> ArchiveKey {
+        ArchiveKey {
+            coordinate: TileCoordinate::new(0, 2, x, 1),
+            extension: "bin".to_string(),
+        }
+    }
+
+    fn read(archive: &mut TileArchive, key: &ArchiveKey) -> Vec<u8> {
+        let (mmap, range) = archive.read(key).unwrap();
+        mmap[range].to_vec()
+    }
+
+    fn archive_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "bevy_terrain_{name}_{}.archive",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn archive_reopen() {
+        let path = archive_path("reopen");
+
+        let mut archive = TileArchive::open(path.clone()).unwrap();
+        archive.write(key(0), &[1, 2, 3]).unwrap();
+        archive.write(key(1), &[4, 5]).unwrap();
+        assert_eq!(read(&mut archive, &key(0)), [1, 2, 3]);
+
+        // a later record supersedes the previous one of the same tile
+        archive.write(key(0), &[6]).unwrap();
+        drop(archive);
+
+        let mut archive = TileArchive::open(path.clone()).unwrap();
+        assert_eq!(read(&mut archive, &key(0)), [6]);
+        assert_eq!(read(&mut archive, &key(1)), [4, 5]);
+        assert!(archive.read(&key(2)).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn archive_truncated_tail() {
+        let path = archive_path("truncated");
+
+        let mut archive = TileArchive::open(path.clone()).unwrap();
+        archive.write(key(0), &[1, 2, 3]).unwrap();
+        archive.write(key(1), &[4, 5, 6, 7]).unwrap();
+        drop(archive);
+
+        // simulate a crash during the save of the last record
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 2)
+            .unwrap();
+
+        let mut archive = TileArchive::open(path.clone()).unwrap();
+        assert_eq!(read(&mut archive, &key(0)), [1, 2, 3]);
+        assert!(archive.read(&key(1)).is_err());
+
+        // appending after the truncated record keeps the archive readable
+        archive.write(key(2), &[8, 9]).unwrap();
+        drop(archive);
+
+        let mut archive = TileArchive::open(path.clone()).unwrap();
+        assert_eq!(read(&mut archive, &key(0)), [1, 2, 3]);
+        assert_eq!(read(&mut archive, &key(2)), [8, 9]);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,17 +1,30 @@
 use crate::{
     math::{Coordinate, TerrainModel, TileCoordinate},
-    terrain_data::{sample_height, tile_atlas::TileAtlas, INVALID_ATLAS_INDEX, INVALID_LOD},
-    terrain_view::{TerrainViewComponents, TerrainViewConfig},
+    terrain_data::{
+        request_policy::{RequestContext, RequestPolicy, TileRequest},
+        sample_height,
+        tile_atlas::TileAtlas,
+        INVALID_ATLAS_INDEX, INVALID_LOD,
+    },
+    terrain_view::{
+        TerrainProbe, TerrainProbeConfig, TerrainView, TerrainViewComponents, TerrainViewConfig,
+    },
     util::inverse_mix,
 };
 use bevy::{
+    ecs::entity::Entities,
     math::{DVec2, DVec3},
     prelude::*,
+    render::primitives::Frustum,
+    utils::HashSet,
 };
 use bytemuck::{Pod, Zeroable};
 use itertools::iproduct;
 use ndarray::{Array2, Array4};
-use std::iter;
+use std::{collections::VecDeque, iter, sync::Arc};
+
+/// The time span in seconds, over which the velocity of the view is estimated.
+const VELOCITY_WINDOW: f64 = 0.25;
 
 /// The current state of a tile of a [`TileTree`].
 ///
@@ -128,6 +141,17 @@ pub struct TileTree {
     pub(crate) origin_lod: u32,
     pub(crate) view_world_position: DVec3,
     pub(crate) approximate_height: f32,
+    pub(crate) prefetch_time: f64,
+    pub(crate) prefetch_samples: u32,
+    /// Whether the terrain is rendered for the view of this tile_tree.
+    /// Tile trees of [`TerrainProbe`](crate::terrain_view::TerrainProbe)s only request tiles.
+    pub(crate) rendered: bool,
+    /// Decides which tiles are requested from the tile atlas.
+    pub(crate) request_policy: Arc<dyn RequestPolicy>,
+    /// The recent view positions and their timestamps, used to estimate the view velocity.
+    view_history: VecDeque<(f64, DVec3)>,
+    /// The tiles requested along the predicted trajectory of the view.
+    prefetched_tiles: HashSet<TileCoordinate>,
 }
 
 impl TileTree {
@@ -153,6 +177,12 @@ impl TileTree {
             precision_threshold_distance: view_config.precision_threshold_distance * scale,
             origin_lod: view_config.origin_lod,
             view_world_position: default(),
+            prefetch_time: view_config.prefetch_time,
+            prefetch_samples: view_config.prefetch_samples,
+            rendered: true,
+            request_policy: view_config.request_policy.clone(),
+            view_history: default(),
+            prefetched_tiles: default(),
             approximate_height: (model.min_height + model.max_height) / 2.0,
             origins: Array2::default((model.side_count() as usize, tile_atlas.lod_count as usize)),
             data: Array4::default((
@@ -172,6 +202,26 @@ impl TileTree {
         }
     }
 
+    /// Creates a new tile_tree from a terrain and a terrain probe config.
+    /// The tile_tree only requests tiles and is not rendered.
+    pub fn new_probe(tile_atlas: &TileAtlas, probe_config: &TerrainProbeConfig) -> Self {
+        let view_config = TerrainViewConfig {
+            tree_size: probe_config.tree_size,
+            load_distance: probe_config.load_distance,
+            blend_distance: probe_config.blend_distance,
+            blend_range: probe_config.blend_range,
+            prefetch_time: probe_config.prefetch_time,
+            prefetch_samples: probe_config.prefetch_samples,
+            request_policy: probe_config.request_policy.clone(),
+            ..default()
+        };
+
+        Self {
+            rendered: false,
+            ..Self::new(tile_atlas, &view_config)
+        }
+    }
+
     fn compute_tree_xy(coordinate: Coordinate, tile_count: f64) -> DVec2 {
         // scale and clamp the coordinate to the tile tree bounds
         (coordinate.uv * tile_count).min(DVec2::splat(tile_count - 0.000001))
@@ -194,6 +244,7 @@ impl TileTree {
         &self,
         tile: TileCoordinate,
         view_coordinate: Coordinate,
+        view_world_position: DVec3,
         model: &TerrainModel,
     ) -> f64 {
         let tile_count = TileCoordinate::count(tile.lod) as f64;
@@ -217,7 +268,23 @@ impl TileTree {
             Coordinate::new(tile.side, (tile_xy.as_dvec2() + offset) / tile_count)
                 .world_position(model, self.approximate_height);
 
-        tile_world_position.distance(self.view_world_position)
+        tile_world_position.distance(view_world_position)
+    }
+
+    /// The load priority of the tile, which is its distance to the view relative to
+    /// the load distance of its LOD.
+    ///
+    /// Coarse tiles and tiles close to the view cover a larger portion of the screen
+    /// and thus have a lower value.
+    pub(super) fn load_priority(&self, tile: TileCoordinate, model: &TerrainModel) -> f64 {
+        let view_coordinate = Coordinate::from_world_position(self.view_world_position, model)
+            .project_to_side(tile.side, model);
+
+        let tile_distance =
+            self.compute_tile_distance(tile, view_coordinate, self.view_world_position, model);
+        let load_distance = self.load_distance / TileCoordinate::count(tile.lod) as f64;
+
+        tile_distance / load_distance
     }
 
     pub(super) fn compute_blend(&self, sample_world_position: DVec3) -> (u32, f32) {
@@ -265,12 +332,126 @@ impl TileTree {
         }
     }
 
-    fn update(&mut self, view_position: DVec3, tile_atlas: &TileAtlas) {
+    /// Estimates the velocity of the view from its recent positions.
+    fn view_velocity(&mut self, view_position: DVec3, time: f64) -> DVec3 {
+        self.view_history.push_back((time, view_position));
+
+        while self
+            .view_history
+            .front()
+            .is_some_and(|&(start, _)| time - start > VELOCITY_WINDOW)
+        {
+            self.view_history.pop_front();
+        }
+
+        let (start_time, start_position) = self.view_history[0];
+
+        if time > start_time {
+            (view_position - start_position) / (time - start_time)
+        } else {
+            DVec3::ZERO
+        }
+    }
+
+    /// Requests the tiles along the extrapolated trajectory of the view, so that they are
+    /// already loaded once the view arrives.
+    ///
+    /// Prefetching only uses half of the unused atlas indices and pauses while the atlas
+    /// is exhausted, so that it never displaces tiles required by the views.
+    fn prefetch(&mut self, view_position: DVec3, time: f64, tile_atlas: &TileAtlas) {
+        let model = &tile_atlas.model;
+        let velocity = self.view_velocity(view_position, time);
+
+        let capacity = tile_atlas.state.prefetch_capacity();
+        let max_tiles = self.prefetched_tiles.len() + capacity / 2;
+        let radius = (self.load_distance / model.scale()).ceil() as i32;
+
+        let mut prefetch_tiles = HashSet::default();
+
+        if capacity > 0 && velocity != DVec3::ZERO {
+            'samples: for sample in 1..=self.prefetch_samples {
+                let predicted_position = view_position
+                    + velocity * self.prefetch_time * sample as f64 / self.prefetch_samples as f64;
+                let coordinate = Coordinate::from_world_position(predicted_position, model);
+
+                for lod in 0..self.lod_count {
+                    let tile_count = TileCoordinate::count(lod);
+                    let center = Self::compute_tree_xy(coordinate, tile_count as f64).as_ivec2();
+
+                    for (x, y) in iproduct!(-radius..=radius, -radius..=radius) {
+                        let (x, y) = (center.x + x, center.y + y);
+
+                        if x < 0 || y < 0 || x >= tile_count as i32 || y >= tile_count as i32 {
+                            continue;
+                        }
+
+                        let tile = TileCoordinate::new(coordinate.side, lod, x as u32, y as u32);
+
+                        let tile_distance =
+                            self.compute_tile_distance(tile, coordinate, predicted_position, model);
+
+                        if tile_distance < self.load_distance / tile_count as f64
+                            && tile_atlas.state.existing_tiles.contains(&tile)
+                            && !self.is_requested(tile)
+                        {
+                            prefetch_tiles.insert(tile);
+
+                            if prefetch_tiles.len() >= max_tiles {
+                                break 'samples;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        self.released_tiles
+            .extend(self.prefetched_tiles.difference(&prefetch_tiles));
+        self.requested_tiles
+            .extend(prefetch_tiles.difference(&self.prefetched_tiles));
+        self.prefetched_tiles = prefetch_tiles;
+    }
+
+    /// Whether the tile is requested by the tile tree itself.
+    fn is_requested(&self, tile: TileCoordinate) -> bool {
+        if tile.lod >= self.lod_count {
+            return false;
+        }
+
+        let entry = &self.tiles[[
+            tile.side as usize,
+            tile.lod as usize,
+            (tile.x % self.tree_size) as usize,
+            (tile.y % self.tree_size) as usize,
+        ]];
+
+        entry.coordinate == tile && entry.state == RequestState::Requested
+    }
+
+    fn update(
+        &mut self,
+        view_position: DVec3,
+        time: f64,
+        frustum: Option<(&Frustum, DVec3)>,
+        projection_scale: Option<f64>,
+        tile_atlas: &TileAtlas,
+    ) {
         let model = &tile_atlas.model;
         self.view_world_position = view_position;
 
         let view_coordinate = Coordinate::from_world_position(self.view_world_position, model);
 
+        let context = RequestContext {
+            model,
+            view_position,
+            altitude: view_position
+                .distance(model.surface_position(view_position, self.approximate_height as f64)),
+            approximate_height: self.approximate_height,
+            projection_scale,
+            frustum: frustum.map(|(frustum, _)| frustum),
+            frustum_offset: frustum.map_or(DVec3::ZERO, |(_, offset)| offset),
+        };
+
         for side in 0..model.side_count() {
             let view_coordinate = view_coordinate.project_to_side(side, model);
 
@@ -286,12 +467,23 @@ impl TileTree {
                         y: origin.y + y,
                     };
 
-                    let tile_distance =
-                        self.compute_tile_distance(tile_coordinate, view_coordinate, model);
+                    let tile_distance = self.compute_tile_distance(
+                        tile_coordinate,
+                        view_coordinate,
+                        self.view_world_position,
+                        model,
+                    );
                     let load_distance =
                         self.load_distance / TileCoordinate::count(tile_coordinate.lod) as f64;
 
-                    let state = if lod == 0 || tile_distance < load_distance {
+                    let request = TileRequest {
+                        coordinate: tile_coordinate,
+                        distance: tile_distance,
+                        load_distance,
+                    };
+
+                    let state = if lod == 0 || self.request_policy.is_requested(&request, &context)
+                    {
                         RequestState::Requested
                     } else {
                         RequestState::Released
@@ -330,6 +522,66 @@ impl TileTree {
                 }
             }
         }
+
+        if self.prefetch_time > 0.0 {
+            self.prefetch(view_position, time, tile_atlas);
+        }
+    }
+
+    /// Releases all tiles requested by this tile_tree from the tile atlas.
+    fn release_all(&mut self, tile_atlas: &mut TileAtlas) {
+        // apply the pending requests first, so that the request counts stay balanced
+        for tile_coordinate in self.released_tiles.drain(..) {
+            tile_atlas.state.release_tile(tile_coordinate);
+        }
+
+        for tile_coordinate in self.requested_tiles.drain(..) {
+            tile_atlas.state.request_tile(tile_coordinate);
+        }
+
+        for tile in &self.tiles {
+            if tile.state == RequestState::Requested {
+                tile_atlas.state.release_tile(tile.coordinate);
+            }
+        }
+
+        for &tile_coordinate in &self.prefetched_tiles {
+            tile_atlas.state.release_tile(tile_coordinate);
+        }
+    }
+
+    /// Removes the tile_trees of despawned terrains and views, as well as the ones of views,
+    /// whose [`TerrainView`] or [`TerrainProbe`] component has been removed.
+    /// The tiles of tile_trees, whose terrain is still present, are released.
+    pub(crate) fn remove_despawned(
+        mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
+        mut tile_atlases: Query<&mut TileAtlas>,
+        mut removed_views: RemovedComponents<TerrainView>,
+        mut removed_probes: RemovedComponents<TerrainProbe>,
+        entities: &Entities,
+    ) {
+        let removed_views = removed_views.read().collect::<HashSet<_>>();
+        let removed_probes = removed_probes.read().collect::<HashSet<_>>();
+
+        tile_trees.retain(|&(terrain, view), tile_tree| {
+            let Ok(mut tile_atlas) = tile_atlases.get_mut(terrain) else {
+                return false;
+            };
+
+            let removed = if tile_tree.rendered {
+                removed_views.contains(&view)
+            } else {
+                removed_probes.contains(&view)
+            };
+
+            if entities.contains(view) && !removed {
+                return true;
+            }
+
+            tile_tree.release_all(&mut tile_atlas);
+
+            false
+        });
     }
 
     /// Traverses all tile_trees and updates the tile states,
@@ -337,11 +589,13 @@ impl TileTree {
     pub(crate) fn compute_requests(
         mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
         tile_atlases: Query<&TileAtlas>,
+        time: Res<Time>,
         #[cfg(feature = "high_precision")] frames: crate::big_space::ReferenceFrames,
         #[cfg(feature = "high_precision")] view_transforms: Query<
             crate::big_space::GridTransformReadOnly,
         >,
         #[cfg(not(feature = "high_precision"))] view_transforms: Query<&Transform>,
+        cameras: Query<(&GlobalTransform, &Frustum, &Camera)>,
     ) {
         for (&(terrain, view), tile_tree) in tile_trees.iter_mut() {
             let tile_atlas = tile_atlases.get(terrain).unwrap();
@@ -354,7 +608,28 @@ impl TileTree {
             #[cfg(not(feature = "high_precision"))]
             let view_position = view_transform.translation.as_dvec3();
 
-            tile_tree.update(view_position, tile_atlas);
+            let camera = cameras.get(view).ok();
+
+            // the frustum is relative to the global transform of the camera
+            let frustum = camera.map(|(global_transform, frustum, _)| {
+                (frustum, global_transform.translation().as_dvec3())
+            });
+            let projection_scale = camera.and_then(|(_, _, camera)| {
+                let viewport_size = camera.logical_viewport_size()?;
+                let clip_from_view = camera.clip_from_view();
+
+                // only perspective projections scale objects by their distance
+                (clip_from_view.w_axis.w == 0.0)
+                    .then(|| clip_from_view.y_axis.y as f64 * viewport_size.y as f64 / 2.0)
+            });
+
+            tile_tree.update(
+                view_position,
+                time.elapsed_seconds_f64(),
+                frustum,
+                projection_scale,
+                tile_atlas,
+            );
         }
     }
 
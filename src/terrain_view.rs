@@ -1,6 +1,12 @@
 //! Types for configuring terrain views.
 
-use bevy::{prelude::*, utils::HashMap};
+use crate::terrain_data::{
+    request_policy::{DistancePolicy, RequestPolicy},
+    tile_atlas::TileAtlas,
+    tile_tree::TileTree,
+};
+use bevy::{prelude::*, render::Extract, utils::HashMap};
+use std::sync::Arc;
 
 /// Resource that stores components that are associated to a terrain entity and a view entity.
 #[derive(Deref, DerefMut, Resource)]
@@ -12,6 +18,34 @@ impl<C> Default for TerrainViewComponents<C> {
     }
 }
 
+impl<C: Send + Sync + 'static> TerrainViewComponents<C> {
+    /// Removes the components of despawned terrains and views.
+    pub(crate) fn remove_despawned(
+        mut components: ResMut<Self>,
+        tile_trees: Extract<Res<TerrainViewComponents<TileTree>>>,
+    ) {
+        components.retain(|key, _| tile_trees.contains_key(key));
+    }
+}
+
+/// Creates the tile tree of each pair of view and terrain, which does not have one yet.
+fn create_tile_trees<V: Component>(
+    tile_trees: &mut TerrainViewComponents<TileTree>,
+    views: &Query<(Entity, &V)>,
+    tile_atlases: &Query<(Entity, &TileAtlas)>,
+    new_tile_tree: impl Fn(&TileAtlas, &V) -> TileTree,
+) {
+    for ((view, component), (terrain, tile_atlas)) in views.iter().flat_map(|view| {
+        tile_atlases
+            .iter()
+            .map(move |tile_atlas| (view, tile_atlas))
+    }) {
+        tile_trees
+            .entry((terrain, view))
+            .or_insert_with(|| new_tile_tree(tile_atlas, component));
+    }
+}
+
 /// The configuration of a terrain view.
 ///
 /// A terrain view describes the quality settings the corresponding terrain will be rendered with.
@@ -42,6 +76,112 @@ pub struct TerrainViewConfig {
     /// The blend percentage in the vertex and fragment shader.
     pub blend_range: f32,
     pub origin_lod: u32,
+    /// The time in seconds the trajectory of the view is extrapolated, to prefetch the tiles
+    /// along its path. Prefetching is disabled, if this is zero.
+    pub prefetch_time: f64,
+    /// The count of positions along the predicted trajectory, around which tiles are prefetched.
+    pub prefetch_samples: u32,
+    /// Decides which tiles are requested, e.g. only the ones inside the view frustum.
+    pub request_policy: Arc<dyn RequestPolicy>,
+}
+
+/// Marks an entity (e.g. a camera) as a view of all terrains.
+///
+/// A [`TileTree`] is created automatically for each terrain, including terrains spawned later.
+/// The config is only read, when the tile trees are created.
+#[derive(Component, Clone, Default)]
+pub struct TerrainView {
+    /// The quality settings the terrains are rendered with for this view.
+    pub config: TerrainViewConfig,
+}
+
+impl TerrainView {
+    pub fn new(config: TerrainViewConfig) -> Self {
+        Self { config }
+    }
+
+    /// Creates the missing tile trees of all terrain views.
+    pub(crate) fn create_tile_trees(
+        mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
+        views: Query<(Entity, &TerrainView)>,
+        tile_atlases: Query<(Entity, &TileAtlas)>,
+    ) {
+        create_tile_trees(
+            &mut tile_trees,
+            &views,
+            &tile_atlases,
+            |tile_atlas, view| TileTree::new(tile_atlas, &view.config),
+        );
+    }
+}
+
+/// The configuration of a terrain probe.
+///
+/// A terrain probe only requests the tiles around an entity, so that the terrain data can be
+/// sampled there, e.g. for physics, AI or audio. It is not rendered.
+#[derive(Clone)]
+pub struct TerrainProbeConfig {
+    /// The count of tiles in x and y direction per tile tree layer.
+    pub tree_size: u32,
+    pub load_distance: f64,
+    pub blend_distance: f64,
+    /// The blend percentage used when sampling the terrain.
+    pub blend_range: f32,
+    /// The time in seconds the trajectory of the probe is extrapolated, to prefetch the tiles
+    /// along its path. Prefetching is disabled, if this is zero.
+    pub prefetch_time: f64,
+    /// The count of positions along the predicted trajectory, around which tiles are prefetched.
+    pub prefetch_samples: u32,
+    /// Decides which tiles are requested around the probe.
+    pub request_policy: Arc<dyn RequestPolicy>,
+}
+
+impl Default for TerrainProbeConfig {
+    fn default() -> Self {
+        let view_config = TerrainViewConfig::default();
+
+        Self {
+            tree_size: view_config.tree_size,
+            load_distance: view_config.load_distance,
+            blend_distance: view_config.blend_distance,
+            blend_range: view_config.blend_range,
+            prefetch_time: view_config.prefetch_time,
+            prefetch_samples: view_config.prefetch_samples,
+            request_policy: view_config.request_policy,
+        }
+    }
+}
+
+/// Marks an entity with a transform (e.g. a vehicle or a remote player) as a point of interest
+/// of all terrains.
+///
+/// A [`TileTree`] is created automatically for each terrain, which requests the tiles around
+/// the entity, but does not take part in rendering.
+/// Use [`sample_height`](crate::terrain_data::sample_height) with this tile tree to access the
+/// terrain data. The config is only read, when the tile trees are created.
+#[derive(Component, Clone, Default)]
+pub struct TerrainProbe {
+    pub config: TerrainProbeConfig,
+}
+
+impl TerrainProbe {
+    pub fn new(config: TerrainProbeConfig) -> Self {
+        Self { config }
+    }
+
+    /// Creates the missing tile trees of all terrain probes.
+    pub(crate) fn create_tile_trees(
+        mut tile_trees: ResMut<TerrainViewComponents<TileTree>>,
+        probes: Query<(Entity, &TerrainProbe)>,
+        tile_atlases: Query<(Entity, &TileAtlas)>,
+    ) {
+        create_tile_trees(
+            &mut tile_trees,
+            &probes,
+            &tile_atlases,
+            |tile_atlas, probe| TileTree::new_probe(tile_atlas, &probe.config),
+        );
+    }
 }
 
 impl Default for TerrainViewConfig {
@@ -59,6 +199,9 @@ impl Default for TerrainViewConfig {
             blend_range: 0.2,
             precision_threshold_distance: 0.001,
             origin_lod: 10,
+            prefetch_time: 0.0,
+            prefetch_samples: 4,
+            request_policy: Arc::new(DistancePolicy),
         }
     }
 }